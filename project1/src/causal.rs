@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use crate::{happened_before, Event, RECV_EVENT, SEND_EVENT};
+
+/// A message as pair of its send and receive event.
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub send: Event,
+    pub recv: Event,
+}
+
+/// Matches every receive event with its send event, events need assigned vector clocks.
+pub fn messages(events: &[Event]) -> Vec<Message> {
+    let sends: HashMap<(String, usize), &Event> = events
+        .iter()
+        .filter(|e| e.title.eq(SEND_EVENT))
+        .map(|e| ((e.host.clone(), e.clock), e))
+        .collect();
+    events
+        .iter()
+        .filter(|e| e.title.eq(RECV_EVENT))
+        .map(|recv| Message {
+            send: (*sends.get(recv.sender_clock.as_ref().unwrap()).unwrap()).clone(),
+            recv: recv.clone(),
+        })
+        .collect()
+}
+
+/// Returns every pair of messages to the same host where the first was delivered
+/// before the second although the send of the second causally precedes the send of the first.
+pub fn causal_violations(messages: &[Message]) -> Vec<(Message, Message)> {
+    let mut violations = Vec::new();
+    for m1 in messages {
+        for m2 in messages {
+            if m1.recv.host.eq(&m2.recv.host)
+                & (m1.recv.clock < m2.recv.clock)
+                & happened_before(&m2.send, &m1.send)
+            {
+                violations.push((m1.clone(), m2.clone()));
+            }
+        }
+    }
+    violations
+}
+
+/// Returns every pair of messages on the same channel delivered in a different order than sent.
+pub fn fifo_violations(messages: &[Message]) -> Vec<(Message, Message)> {
+    let mut violations = Vec::new();
+    for m1 in messages {
+        for m2 in messages {
+            if m1.recv.host.eq(&m2.recv.host)
+                & m1.send.host.eq(&m2.send.host)
+                & (m1.recv.clock < m2.recv.clock)
+                & (m2.send.clock < m1.send.clock)
+            {
+                violations.push((m1.clone(), m2.clone()));
+            }
+        }
+    }
+    violations
+}
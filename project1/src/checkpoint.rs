@@ -0,0 +1,95 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::error::Error;
+
+use crate::{Event, CHECKPOINT_EVENT, RECV_EVENT};
+
+/// Simulates the Koo-Toueg coordinated checkpointing protocol initiated by `initiator`
+/// right after its event with clock `clock`. Returns the trace with the resulting permanent
+/// checkpoints inserted as new checkpoint events, clocks of later events are shifted accordingly.
+pub fn koo_toueg(
+    events: &[Event],
+    hosts: &[String],
+    initiator: &str,
+    clock: usize,
+) -> Result<Vec<Event>, Box<dyn Error>> {
+    let init_idx = events
+        .iter()
+        .position(|e| e.host.eq(initiator) & (e.clock == clock))
+        .ok_or_else(|| format!("initiation event {initiator}:{clock} not found in log"))?;
+    let prefix = &events[..=init_idx];
+
+    // last checkpoint and number of events per host at the time of initiation
+    let mut last_checkpoint: HashMap<&str, usize> = hosts.iter().map(|h| (h.as_str(), 0)).collect();
+    let mut current_clock: HashMap<&str, usize> = hosts.iter().map(|h| (h.as_str(), 0)).collect();
+    for event in prefix {
+        if event.title.eq(CHECKPOINT_EVENT) {
+            last_checkpoint.insert(&event.host, event.clock);
+        }
+        current_clock.insert(&event.host, event.clock);
+    }
+
+    // host i depends on host j if i received a message since its last checkpoint
+    // which j sent after its own last checkpoint
+    let mut dependencies: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+    for event in prefix.iter().filter(|e| e.title.eq(RECV_EVENT)) {
+        let (sender, send_clock) = event.sender_clock.as_ref().unwrap();
+        if (event.clock > last_checkpoint[event.host.as_str()])
+            & (*send_clock > last_checkpoint[sender.as_str()])
+        {
+            dependencies.entry(&event.host).or_default().insert(sender);
+        }
+    }
+
+    // phase 1: propagate checkpoint requests, every requested host takes a tentative checkpoint
+    let mut tentative = BTreeSet::from([initiator]);
+    let mut requests = VecDeque::from([initiator]);
+    println!("{initiator} takes tentative checkpoint");
+    while let Some(host) = requests.pop_front() {
+        for &dep in dependencies.get(host).into_iter().flatten() {
+            println!("{host} requests checkpoint from {dep}");
+            if tentative.insert(dep) {
+                println!("{dep} takes tentative checkpoint");
+                requests.push_back(dep);
+            }
+        }
+    }
+
+    // phase 2: all hosts agreed, the initiator makes every tentative checkpoint permanent
+    println!("{initiator} commits, tentative checkpoints of {tentative:?} become permanent");
+
+    let mut augmented = prefix.to_vec();
+    for host in tentative.iter() {
+        augmented.push(Event {
+            title: CHECKPOINT_EVENT.to_owned(),
+            vec_clock: Vec::new(),
+            host: host.to_string(),
+            clock: current_clock[host] + 1,
+            sender_clock: None,
+        });
+    }
+    // events after the inserted checkpoints are shifted by one on the checkpointed hosts
+    augmented.extend(events[init_idx + 1..].iter().cloned().map(|mut e| {
+        if tentative.contains(e.host.as_str()) {
+            e.clock += 1;
+        }
+        if let Some((sender, send_clock)) = e.sender_clock.as_mut() {
+            if tentative.contains(sender.as_str()) & (*send_clock > current_clock[sender.as_str()])
+            {
+                *send_clock += 1;
+            }
+        }
+        e
+    }));
+    Ok(augmented)
+}
+
+/// Number of events each host has to undo when rolling back to `recovery_line`.
+pub fn rollback(recovery_line: &[Event], events: &[Event]) -> HashMap<String, usize> {
+    recovery_line
+        .iter()
+        .map(|e| {
+            let len = events.iter().filter(|x| x.host.eq(&e.host)).count();
+            (e.host.clone(), len - e.clock)
+        })
+        .collect()
+}
@@ -0,0 +1,126 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    error::Error,
+    fs::{read_dir, read_to_string},
+    path::PathBuf,
+};
+
+use crate::{
+    parse_events, Event, CHECKPOINT_EVENT, JOIN_EVENT, LEAVE_EVENT, RECV_EVENT, SEND_EVENT,
+};
+
+/// Communication pattern of a trace, the sequence of sends, receives and checkpoints per host
+/// with the peer of every message but without any clocks. Messages are numbered by the position
+/// of their send among the sends of the sender, so traces where a receive matched another send,
+/// as in a message race, have different signatures.
+type Signature = BTreeMap<String, Vec<String>>;
+
+fn signature(events: &[Event]) -> Signature {
+    let receivers: HashMap<(String, usize), String> = events
+        .iter()
+        .filter(|e| e.title.eq(RECV_EVENT))
+        .map(|e| (e.sender_clock.clone().unwrap(), e.host.clone()))
+        .collect();
+    let mut sent: HashMap<&str, usize> = HashMap::new();
+    let sends: HashMap<(String, usize), usize> = events
+        .iter()
+        .filter(|e| e.title.eq(SEND_EVENT))
+        .map(|e| {
+            let n = sent.entry(&e.host).or_default();
+            *n += 1;
+            ((e.host.clone(), e.clock), *n)
+        })
+        .collect();
+
+    let mut signature = Signature::new();
+    for event in events {
+        let token = if event.title.eq(SEND_EVENT) {
+            let receiver = receivers.get(&(event.host.clone(), event.clock));
+            format!("S>{}", receiver.map(String::as_str).unwrap_or("?"))
+        } else if event.title.eq(RECV_EVENT) {
+            let sender_clock = event.sender_clock.as_ref().unwrap();
+            match sends.get(sender_clock) {
+                Some(n) => format!("R<{}{n}", sender_clock.0),
+                None => format!("R<{}?", sender_clock.0),
+            }
+        } else if event.title.eq(CHECKPOINT_EVENT) {
+            String::from("C")
+        } else if event.title.eq(JOIN_EVENT) {
+            String::from("J")
+        } else if event.title.eq(LEAVE_EVENT) {
+            String::from("L")
+        } else {
+            String::from("o")
+        };
+        signature.entry(event.host.clone()).or_default().push(token);
+    }
+    signature
+}
+
+fn edit_distance(a: &[String], b: &[String]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.iter().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, y) in b.iter().enumerate() {
+            let cost = if x.eq(y) { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+    prev[b.len()]
+}
+
+/// Sum of the per host edit distances normalized by the length of the longer trace.
+fn distance(a: &Signature, b: &Signature) -> f64 {
+    let empty = Vec::new();
+    let hosts: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+    let dist: usize = hosts
+        .into_iter()
+        .map(|h| edit_distance(a.get(h).unwrap_or(&empty), b.get(h).unwrap_or(&empty)))
+        .sum();
+    let len = a
+        .values()
+        .map(Vec::len)
+        .sum::<usize>()
+        .max(b.values().map(Vec::len).sum());
+    if len == 0 {
+        0.0
+    } else {
+        dist as f64 / len as f64
+    }
+}
+
+/// Clusters all logs in `dir`, every trace joins the first cluster whose representative
+/// is within `threshold`, otherwise it becomes the representative of a new cluster.
+pub fn run(dir: &str, threshold: f64) -> Result<(), Box<dyn Error>> {
+    let mut paths: Vec<PathBuf> = read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+    paths.retain(|p| p.is_file());
+    paths.sort();
+
+    let mut clusters: Vec<(Signature, Vec<PathBuf>)> = Vec::new();
+    for path in paths.iter() {
+        let signature = signature(&parse_events(&read_to_string(path)?)?);
+        match clusters
+            .iter_mut()
+            .find(|(repr, _)| distance(repr, &signature) <= threshold)
+        {
+            Some((_, members)) => members.push(path.clone()),
+            None => clusters.push((signature, vec![path.clone()])),
+        }
+    }
+
+    println!("Number of traces {}", paths.len());
+    println!("Number of clusters {}", clusters.len());
+    clusters.sort_by(|a, b| Ord::cmp(&b.1.len(), &a.1.len()));
+    for (i, (_, members)) in clusters.iter().enumerate() {
+        println!(
+            "Cluster {i} with {} traces ({:.1}%), representative {}",
+            members.len(),
+            100.0 * members.len() as f64 / paths.len() as f64,
+            members[0].display()
+        );
+    }
+    Ok(())
+}
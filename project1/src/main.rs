@@ -1,4 +1,4 @@
-use clap::{arg, command};
+use clap::{arg, command, value_parser};
 use itertools::Itertools;
 use plotters::coord::types::RangedCoordu32;
 use plotters::prelude::*;
@@ -7,6 +7,13 @@ use std::error::Error;
 use std::fs::read_to_string;
 use std::str::FromStr;
 
+mod causal;
+mod checkpoint;
+mod cluster;
+mod stats;
+mod stream;
+mod tui;
+
 const HLINE_PAD_X: u32 = 30;
 const HLINE_PAD_Y: u32 = 100;
 const EVENT_PAD_X: u32 = 50;
@@ -15,6 +22,8 @@ const FONT_SIZE: f32 = 20.0;
 const SEND_EVENT: &str = "Send event";
 const RECV_EVENT: &str = "Receive event";
 const CHECKPOINT_EVENT: &str = "Checkpoint";
+const JOIN_EVENT: &str = "Join event";
+const LEAVE_EVENT: &str = "Leave event";
 
 #[derive(Debug, Clone)]
 struct Event {
@@ -29,15 +38,22 @@ impl FromStr for Event {
     type Err = Box<dyn Error>;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (caption, host_clock) = s.split_once('\n').unwrap();
-        let (host, clocks) = host_clock.split_once(' ').unwrap();
-        let clocks: HashMap<String, usize> = serde_json::from_str(clocks).unwrap();
-        let clock = *clocks.get(host).unwrap();
+        let (caption, host_clock) = s.split_once('\n').ok_or("missing host line")?;
+        let (host, clocks) = host_clock
+            .split_once(' ')
+            .ok_or_else(|| format!("missing clock in {host_clock:?}"))?;
+        let clocks: HashMap<String, usize> = serde_json::from_str(clocks)?;
+        let clock = *clocks
+            .get(host)
+            .ok_or_else(|| format!("missing clock of host {host}"))?;
         let sender_clock: Vec<(String, usize)> = clocks
             .into_iter()
             .filter(|(h, _)| !h.to_owned().eq(host))
             .collect();
         let sender_clock = sender_clock.first().cloned();
+        if caption.eq(RECV_EVENT) & sender_clock.is_none() {
+            return Err(format!("receive event of {host} without sender clock").into());
+        }
         Ok(Event {
             title: caption.to_owned(),
             vec_clock: Vec::new(),
@@ -48,6 +64,14 @@ impl FromStr for Event {
     }
 }
 
+fn parse_events(data: &str) -> Result<Vec<Event>, Box<dyn Error>> {
+    let events: Vec<&str> = data.lines().collect();
+    events
+        .chunks(2)
+        .map(|e| (e[0].to_owned() + "\n" + e[1]).parse())
+        .collect()
+}
+
 fn draw_event(
     root: &DrawingArea<BitMapBackend, Cartesian2d<RangedCoordu32, RangedCoordu32>>,
     label: String,
@@ -145,6 +169,8 @@ fn visualize(events: &[Event], hosts: &BTreeMap<String, usize>) -> Result<(), Bo
         let label = if event.title.eq(RECV_EVENT)
             | event.title.eq(SEND_EVENT)
             | event.title.eq(CHECKPOINT_EVENT)
+            | event.title.eq(JOIN_EVENT)
+            | event.title.eq(LEAVE_EVENT)
         {
             String::from(event.title.chars().next().unwrap())
         } else {
@@ -167,61 +193,86 @@ fn visualize(events: &[Event], hosts: &BTreeMap<String, usize>) -> Result<(), Bo
     Ok(())
 }
 
-fn assign_vector_clocks(events: &mut Vec<Event>, hosts: &[String]) {
-    let host_idxs: HashMap<String, usize> = hosts
-        .iter()
-        .enumerate()
-        .map(|(i, host)| (host.clone(), i))
-        .collect();
-
-    let mut vclocks = HashMap::new();
-    hosts.iter().for_each(|h| {
-        vclocks.insert(h, vec![0usize; hosts.len()]);
-    });
-
-    // fails if log is not in cronological order
-    let mut messages = HashMap::<(String, usize), Vec<usize>>::new();
+/// Growable vector clocks, a host gets the next index when it joins or first appears
+/// and vectors only grow when they are incremented or merged with a longer one.
+#[derive(Default)]
+struct VectorClocks {
+    hosts: Vec<String>,
+    host_idxs: HashMap<String, usize>,
+    vclocks: HashMap<String, Vec<usize>>,
+    messages: HashMap<(String, usize), Vec<usize>>,
+}
 
-    for event in events {
+impl VectorClocks {
+    /// Assigns the vector clock of `event`, returns false for a receive with unknown send.
+    fn assign(&mut self, event: &mut Event) -> bool {
         let host = event.host.clone();
+        if !self.host_idxs.contains_key(&host) {
+            self.host_idxs.insert(host.clone(), self.hosts.len());
+            self.hosts.push(host.clone());
+        }
+        let idx = self.host_idxs[&host];
+        let vclock = self.vclocks.entry(host.clone()).or_default();
+
         // own vclock += 1
-        vclocks.get_mut(&host).unwrap()[*host_idxs.get(&host).unwrap()] += 1;
+        if vclock.len() <= idx {
+            vclock.resize(idx + 1, 0);
+        }
+        vclock[idx] += 1;
 
+        let mut found = true;
         if event.title.eq(RECV_EVENT) {
-            // get sender and sender_clock from recv event
-            let sender_clock = event.sender_clock.as_ref().unwrap();
-
             // get sender vclock from messages
-            let vec = messages.get(sender_clock).unwrap();
-
-            // update all but own vclock with max(v_msg[j], v[j])
-            hosts.iter().for_each(|h| {
-                if !h.eq(&host) {
-                    let j = *host_idxs.get(h).unwrap();
-                    vclocks.get_mut(&host).unwrap()[j] = vclocks.get(&host).unwrap()[j].max(vec[j]);
+            match self.messages.get(event.sender_clock.as_ref().unwrap()) {
+                Some(vec) => {
+                    // update all but own vclock with max(v_msg[j], v[j])
+                    if vclock.len() < vec.len() {
+                        vclock.resize(vec.len(), 0);
+                    }
+                    for (j, c) in vec.iter().enumerate() {
+                        if j != idx {
+                            vclock[j] = vclock[j].max(*c);
+                        }
+                    }
                 }
-            });
+                None => found = false,
+            }
         } else if event.title.eq(SEND_EVENT) {
             // store sender vclock in messages
-            messages.insert(
-                (host.clone(), event.clock),
-                vclocks.get(&host).unwrap().clone(),
-            );
+            self.messages.insert((host, event.clock), vclock.clone());
         }
 
-        event.vec_clock = vclocks.get(&host).unwrap().clone();
+        event.vec_clock = vclock.clone();
+        found
     }
 }
 
+/// Assigns vector clocks to all events and returns the hosts in the order of their clock index.
+fn assign_vector_clocks(events: &mut [Event]) -> Vec<String> {
+    let mut vclocks = VectorClocks::default();
+    // fails if log is not in cronological order
+    for event in events {
+        assert!(vclocks.assign(event), "receive before send {event:?}");
+    }
+    vclocks.hosts
+}
+
+// entries missing in the shorter vector clock count as 0
+fn clock_pairs<'a>(e1: &'a Event, e2: &'a Event) -> impl Iterator<Item = (usize, usize)> + 'a {
+    (0..e1.vec_clock.len().max(e2.vec_clock.len())).map(|i| {
+        (
+            *e1.vec_clock.get(i).unwrap_or(&0),
+            *e2.vec_clock.get(i).unwrap_or(&0),
+        )
+    })
+}
+
 fn are_concurrent(e1: &Event, e2: &Event) -> bool {
-    e1.vec_clock
-        .iter()
-        .zip(e2.vec_clock.iter())
-        .any(|(e1, e2)| e1 > e2)
-        & e2.vec_clock
-            .iter()
-            .zip(e1.vec_clock.iter())
-            .any(|(e2, e1)| e2 > e1)
+    clock_pairs(e1, e2).any(|(e1, e2)| e1 > e2) & clock_pairs(e1, e2).any(|(e1, e2)| e1 < e2)
+}
+
+fn happened_before(e1: &Event, e2: &Event) -> bool {
+    clock_pairs(e1, e2).all(|(e1, e2)| e1 <= e2) & clock_pairs(e1, e2).any(|(e1, e2)| e1 < e2)
 }
 
 fn count_concurrent_events(events: &[Event]) -> usize {
@@ -260,7 +311,11 @@ fn is_consistent_cut(cut: &[Event], host_events: &HashMap<String, Vec<Event>>) -
     true
 }
 
-fn find_recovery_line(events: &[Event], hosts: &[String], fail: &[String]) -> Vec<Event> {
+fn find_recovery_line(
+    events: &[Event],
+    hosts: &[String],
+    fail: &[String],
+) -> Result<Vec<Event>, Box<dyn Error>> {
     let mut host_events: HashMap<String, Vec<Event>> = hosts
         .iter()
         .map(|h| (h.clone(), Vec::<Event>::new()))
@@ -307,28 +362,39 @@ fn find_recovery_line(events: &[Event], hosts: &[String], fail: &[String]) -> Ve
         })
         .collect();
 
-    recovery_lines_sorted
-        .last()
-        .cloned()
-        .expect("failed to find recovery line")
+    let recovery_line = recovery_lines_sorted.last().cloned().ok_or_else(|| {
+        format!("no recovery line found, every failing host of {fail:?} needs a checkpoint")
+    })?;
+    Ok(recovery_line
         .into_iter()
         .sorted_by(|a, b| Ord::cmp(&a.host, &b.host))
-        .collect()
+        .collect())
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let matches = command!()
         .arg(arg!(<FILE> "Path to log file with events"))
         .arg(arg!(-f --fail <FAIL> "Comma separated list of hosts e.g. Alice,Bob"))
+        .arg(arg!(-c --checkpoint <INIT> "Run coordinated checkpointing initiated at host:clock e.g. Alice:5"))
+        .arg(arg!(-t --tui "Browse the trace interactively in the terminal"))
+        .arg(arg!(-k --cluster "Treat FILE as directory of logs and cluster them by communication pattern"))
+        .arg(
+            arg!(--threshold <DIST> "Maximum normalized distance to a cluster representative")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(arg!(-s --stream "Analyse events as they arrive, follows FILE as it grows or reads stdin for -"))
         .get_matches();
 
     let path = matches.get_one::<String>("FILE").unwrap();
-    let data = read_to_string(path)?;
-    let events: Vec<&str> = data.lines().collect();
-    let mut events: Vec<Event> = events
-        .chunks(2)
-        .map(|e| (e[0].to_owned() + "\n" + e[1]).parse())
-        .collect::<Result<Vec<Event>, _>>()?;
+    if *matches.get_one::<bool>("stream").unwrap_or(&false) {
+        return stream::run(path);
+    }
+    if *matches.get_one::<bool>("cluster").unwrap_or(&false) {
+        let threshold = *matches.get_one::<f64>("threshold").unwrap_or(&0.0);
+        return cluster::run(path, threshold);
+    }
+
+    let mut events = parse_events(&read_to_string(path)?)?;
 
     let mut hosts = BTreeMap::new();
     events.iter().for_each(|e| match hosts.get_mut(&e.host) {
@@ -338,28 +404,104 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     });
 
-    visualize(&events, &hosts)?;
+    if *matches.get_one::<bool>("tui").unwrap_or(&false) {
+        let hosts = assign_vector_clocks(&mut events);
+        return tui::run(&events, &hosts);
+    }
 
-    let hosts: Vec<String> = hosts.into_keys().collect();
+    visualize(&events, &hosts)?;
 
-    assign_vector_clocks(&mut events, &hosts);
+    // the printed clocks are indexed in the order the hosts first appear
+    let hosts = assign_vector_clocks(&mut events);
 
     let count = count_concurrent_events(&events);
     println!("Number of concurrent event pairs {count}");
 
+    stats::report(&events, &hosts)?;
+
+    let messages = causal::messages(&events);
+    let violations = causal::causal_violations(&messages);
+    println!("Number of causal delivery violations {}", violations.len());
+    for (m1, m2) in violations.iter() {
+        println!(
+            "{} received from {} at clock {} {:?} before message from {} at clock {} {:?} which causally precedes it",
+            m1.recv.host,
+            m1.send.host,
+            m1.recv.clock,
+            m1.recv.vec_clock,
+            m2.send.host,
+            m2.recv.clock,
+            m2.recv.vec_clock
+        );
+    }
+    let violations = causal::fifo_violations(&messages);
+    println!("Number of FIFO violations {}", violations.len());
+    for (m1, m2) in violations.iter() {
+        println!(
+            "Channel {} -> {} delivered send at clock {} {:?} before send at clock {} {:?}",
+            m1.send.host,
+            m1.recv.host,
+            m1.send.clock,
+            m1.send.vec_clock,
+            m2.send.clock,
+            m2.send.vec_clock
+        );
+    }
+
     println!("Events with vector clocks");
     for (i, event) in events.iter().enumerate() {
         println!("{i}: {event:?}");
     }
 
-    if let Some(fail) = matches.get_one::<String>("fail") {
-        let fail: Vec<String> = fail.split(',').map(String::from).collect();
+    let fail: Option<Vec<String>> = matches
+        .get_one::<String>("fail")
+        .map(|fail| fail.split(',').map(String::from).collect());
+
+    let recovery_line = if let Some(fail) = &fail {
         println!("Following hosts will fail {fail:?}");
 
-        let recovery_line = find_recovery_line(&events, &hosts, &fail);
+        let recovery_line = find_recovery_line(&events, &hosts, fail)?;
         println!("Found recovery line {recovery_line:?}");
+        Some(recovery_line)
     } else {
         println!("No host will fail, no recovery line created");
+        None
+    };
+
+    if let Some(initiation) = matches.get_one::<String>("checkpoint") {
+        let (initiator, clock) = initiation
+            .split_once(':')
+            .ok_or("invalid initiation point, expected host:clock")?;
+        let clock: usize = clock
+            .parse()
+            .map_err(|e| format!("invalid initiation clock {clock}: {e}"))?;
+        println!("Running coordinated checkpointing initiated by {initiator} at clock {clock}");
+
+        let mut augmented = checkpoint::koo_toueg(&events, &hosts, initiator, clock)?;
+        assign_vector_clocks(&mut augmented);
+
+        // without a fail list, compare the rollback for a failure of the initiator
+        let fail = fail.unwrap_or_else(|| vec![initiator.to_owned()]);
+        let recovery_line = match recovery_line {
+            Some(recovery_line) => recovery_line,
+            None => find_recovery_line(&events, &hosts, &fail)?,
+        };
+        let augmented_line = find_recovery_line(&augmented, &hosts, &fail)?;
+        println!("Found recovery line with coordinated checkpoints {augmented_line:?}");
+
+        let before = checkpoint::rollback(&recovery_line, &events);
+        let after = checkpoint::rollback(&augmented_line, &augmented);
+        for host in hosts.iter() {
+            println!(
+                "{host} rolls back {} events before and {} events after coordinated checkpointing",
+                before[host], after[host]
+            );
+        }
+        println!(
+            "Total rollback {} events before and {} events after coordinated checkpointing",
+            before.values().sum::<usize>(),
+            after.values().sum::<usize>()
+        );
     }
 
     Ok(())
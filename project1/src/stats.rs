@@ -0,0 +1,137 @@
+use plotters::{
+    prelude::*,
+    style::text_anchor::{HPos, Pos, VPos},
+};
+use std::{collections::HashMap, error::Error};
+
+use crate::{Event, CHECKPOINT_EVENT, FONT_SIZE, RECV_EVENT, SEND_EVENT};
+
+const CELL_SIZE: u32 = 80;
+
+/// Prints per host statistics, the host to host message matrix, checkpoint intervals
+/// and the longest causal chain and renders the matrix to matrix.png.
+pub fn report(events: &[Event], hosts: &[String]) -> Result<(), Box<dyn Error>> {
+    let host_idxs: HashMap<&str, usize> = hosts
+        .iter()
+        .enumerate()
+        .map(|(i, h)| (h.as_str(), i))
+        .collect();
+
+    for host in hosts {
+        let count = |title: &str| {
+            events
+                .iter()
+                .filter(|e| e.host.eq(host) & e.title.eq(title))
+                .count()
+        };
+        let checkpoints: Vec<usize> = events
+            .iter()
+            .filter(|e| e.host.eq(host) & e.title.eq(CHECKPOINT_EVENT))
+            .map(|e| e.clock)
+            .collect();
+        // number of events since the previous checkpoint or the start
+        let intervals: Vec<usize> = checkpoints
+            .iter()
+            .scan(0, |last, c| {
+                let interval = c - *last;
+                *last = *c;
+                Some(interval)
+            })
+            .collect();
+        println!(
+            "{host}: {} events, {} sends, {} receives, checkpoint intervals {intervals:?}",
+            events.iter().filter(|e| e.host.eq(host)).count(),
+            count(SEND_EVENT),
+            count(RECV_EVENT),
+        );
+    }
+
+    let mut matrix = vec![vec![0usize; hosts.len()]; hosts.len()];
+    for event in events.iter().filter(|e| e.title.eq(RECV_EVENT)) {
+        let sender = &event.sender_clock.as_ref().unwrap().0;
+        matrix[host_idxs[sender.as_str()]][host_idxs[event.host.as_str()]] += 1;
+    }
+    println!("Messages from row host to column host {hosts:?}");
+    for (host, row) in hosts.iter().zip(matrix.iter()) {
+        println!("{host}: {row:?}");
+    }
+
+    // longest chain of events ending at every event and the messages along it
+    let mut chain: HashMap<(&str, usize), (usize, usize)> = HashMap::new();
+    let mut longest = (0, 0);
+    for event in events {
+        let mut best = (1, 0);
+        if let Some(&(len, hops)) = chain.get(&(event.host.as_str(), event.clock - 1)) {
+            best = (len + 1, hops);
+        }
+        if event.title.eq(RECV_EVENT) {
+            let (sender, clock) = event.sender_clock.as_ref().unwrap();
+            if let Some(&(len, hops)) = chain.get(&(sender.as_str(), *clock)) {
+                best = best.max((len + 1, hops + 1));
+            }
+        }
+        chain.insert((&event.host, event.clock), best);
+        longest = longest.max(best);
+    }
+    println!(
+        "Longest causal chain {} events with {} messages",
+        longest.0, longest.1
+    );
+
+    draw_matrix(&matrix, hosts)
+}
+
+fn draw_matrix(matrix: &[Vec<usize>], hosts: &[String]) -> Result<(), Box<dyn Error>> {
+    let n = hosts.len() as u32;
+    let size = n * CELL_SIZE + 2 * CELL_SIZE;
+    let root = BitMapBackend::new("matrix.png", (size, size)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let mut chart = ChartBuilder::on(&root)
+        .margin(CELL_SIZE / 2)
+        .x_label_area_size(CELL_SIZE / 2)
+        .y_label_area_size(CELL_SIZE)
+        .build_cartesian_2d(
+            (0..n as usize - 1).into_segmented(),
+            (0..n as usize - 1).into_segmented(),
+        )?;
+
+    let label = |v: &SegmentValue<usize>| match v {
+        SegmentValue::CenterOf(i) => hosts.get(*i).cloned().unwrap_or_default(),
+        _ => String::new(),
+    };
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_desc("receiver")
+        .y_desc("sender")
+        .x_label_formatter(&label)
+        .y_label_formatter(&label)
+        .label_style(("sans-serif", FONT_SIZE))
+        .draw()?;
+
+    let max = *matrix.iter().flatten().max().unwrap_or(&0).max(&1);
+    for (i, row) in matrix.iter().enumerate() {
+        for (j, count) in row.iter().enumerate() {
+            let shade = 255 - (255 * count / max) as u8;
+            chart.draw_series(std::iter::once(Rectangle::new(
+                [
+                    (SegmentValue::Exact(j), SegmentValue::Exact(i)),
+                    (SegmentValue::Exact(j + 1), SegmentValue::Exact(i + 1)),
+                ],
+                RGBColor(255, shade, shade).filled(),
+            )))?;
+            chart.draw_series(std::iter::once(Text::new(
+                count.to_string(),
+                (SegmentValue::CenterOf(j), SegmentValue::CenterOf(i)),
+                ("sans-serif", FONT_SIZE)
+                    .into_font()
+                    .into_text_style(&root)
+                    .pos(Pos::new(HPos::Center, VPos::Center)),
+            )))?;
+        }
+    }
+
+    root.present()?;
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    are_concurrent, causal::Message, happened_before, Event, VectorClocks, CHECKPOINT_EVENT,
+    JOIN_EVENT, LEAVE_EVENT, SEND_EVENT,
+};
+
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Incremental analysis state, updated with every event as it arrives.
+#[derive(Default)]
+pub struct Stream {
+    events: Vec<Event>,
+    vclocks: VectorClocks,
+    left: HashSet<String>,
+    sends: HashMap<(String, usize), Event>,
+    received: HashMap<String, Vec<Message>>,
+    checkpoints: BTreeMap<String, usize>,
+    recovery_line: Option<BTreeMap<String, usize>>,
+    concurrent: usize,
+}
+
+impl Stream {
+    /// Assigns the vector clock of `event`, updates the counts and prints alerts.
+    pub fn push(&mut self, mut event: Event) {
+        let host = event.host.clone();
+        if event.title.eq(JOIN_EVENT) {
+            println!("Host {host} joined");
+            self.left.remove(&host);
+        } else if self.left.contains(&host) {
+            println!("Event from departed host {host} without rejoining");
+        } else if !self.vclocks.host_idxs.contains_key(&host) {
+            println!("New host {host}");
+        }
+
+        if !self.vclocks.assign(&mut event) {
+            let (sender, clock) = event.sender_clock.as_ref().unwrap();
+            println!(
+                "Dangling receive at {host} clock {}, no send from {sender} at clock {clock}",
+                event.clock
+            );
+        }
+        let message = event
+            .sender_clock
+            .as_ref()
+            .and_then(|s| self.sends.get(s))
+            .cloned();
+
+        if event.title.eq(SEND_EVENT) {
+            self.sends
+                .insert((host.clone(), event.clock), event.clone());
+        }
+
+        let concurrent = self
+            .events
+            .iter()
+            .filter(|e| are_concurrent(e, &event))
+            .count();
+        self.concurrent += concurrent;
+        println!(
+            "{}: {event:?} concurrent to {concurrent} events, {} concurrent event pairs",
+            self.events.len(),
+            self.concurrent
+        );
+
+        if let Some(send) = message {
+            self.check_delivery(Message {
+                send,
+                recv: event.clone(),
+            });
+        }
+
+        if event.title.eq(CHECKPOINT_EVENT) {
+            self.checkpoints.insert(host, event.clock);
+            self.check_recovery_line();
+        } else if event.title.eq(LEAVE_EVENT) {
+            println!("Host {host} left");
+            self.left.insert(host);
+        }
+
+        self.events.push(event);
+    }
+
+    fn check_delivery(&mut self, message: Message) {
+        let received = self.received.entry(message.recv.host.clone()).or_default();
+        for earlier in received.iter() {
+            if happened_before(&message.send, &earlier.send) {
+                println!(
+                    "Causal delivery violation at {} clock {} {:?}, message from {} at clock {} was delivered before message from {} at clock {} which causally precedes it",
+                    message.recv.host,
+                    message.recv.clock,
+                    message.recv.vec_clock,
+                    earlier.send.host,
+                    earlier.send.clock,
+                    message.send.host,
+                    message.send.clock
+                );
+            }
+            if earlier.send.host.eq(&message.send.host) & (message.send.clock < earlier.send.clock)
+            {
+                println!(
+                    "FIFO violation on channel {} -> {}, send at clock {} delivered before send at clock {}",
+                    message.send.host, message.recv.host, earlier.send.clock, message.send.clock
+                );
+            }
+        }
+        received.push(message);
+    }
+
+    /// Reports when the latest checkpoints of all hosts form a new consistent recovery line,
+    /// hosts without checkpoint roll back to their initial state.
+    fn check_recovery_line(&mut self) {
+        let line: BTreeMap<String, usize> = self
+            .vclocks
+            .hosts
+            .iter()
+            .map(|h| (h.clone(), *self.checkpoints.get(h).unwrap_or(&0)))
+            .collect();
+        let consistent =
+            self.received.values().flatten().all(|m| {
+                (m.recv.clock > line[&m.recv.host]) | (m.send.clock <= line[&m.send.host])
+            });
+        if consistent & self.recovery_line.as_ref().is_none_or(|l| l.ne(&line)) {
+            println!("New recovery line available {line:?}");
+            self.recovery_line = Some(line);
+        }
+    }
+}
+
+/// Reads events from `path` as they arrive, `-` reads from stdin. Files are followed
+/// as they grow like `tail -f`, stdin is read until it is closed. A line is only parsed once
+/// its newline arrived, malformed lines are reported on stderr and skipped.
+pub fn run(path: &str) -> Result<(), Box<dyn Error>> {
+    let follow = !path.eq("-");
+    let mut reader: Box<dyn BufRead> = if follow {
+        Box::new(BufReader::new(File::open(path)?))
+    } else {
+        Box::new(io::stdin().lock())
+    };
+
+    let mut stream = Stream::default();
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    loop {
+        let read = reader.read_line(&mut line)?;
+        if (read == 0) | ((read > 0) & !line.ends_with('\n') & follow) {
+            if !follow {
+                break;
+            }
+            // wait for the rest of the line to be written
+            thread::sleep(FOLLOW_INTERVAL);
+            continue;
+        }
+        lines.push(line.trim_end().to_owned());
+        line.clear();
+
+        if lines.len() == 2 {
+            match lines.join("\n").parse::<Event>() {
+                Ok(event) => {
+                    stream.push(event);
+                    lines.clear();
+                }
+                // drop only the first line, so a single bad line does not shift all later events
+                Err(e) => eprintln!("Skipped malformed line {:?}: {e}", lines.remove(0)),
+            }
+        }
+    }
+
+    println!("Number of events {}", stream.events.len());
+    println!("Number of concurrent event pairs {}", stream.concurrent);
+    Ok(())
+}
@@ -0,0 +1,291 @@
+use crossterm::{
+    cursor,
+    event::{self, Event as TermEvent, KeyCode},
+    style::{self, Stylize},
+    terminal::{
+        self, disable_raw_mode, enable_raw_mode, ClearType, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
+    ExecutableCommand, QueueableCommand,
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    io::{self, Stdout, Write},
+};
+
+use crate::{
+    happened_before, is_consistent_cut, Event, CHECKPOINT_EVENT, JOIN_EVENT, LEAVE_EVENT,
+    RECV_EVENT, SEND_EVENT,
+};
+
+const EVENT_WIDTH: u16 = 3;
+
+#[derive(PartialEq)]
+enum Highlight {
+    None,
+    Causal,
+    Cut,
+}
+
+struct Browser<'a> {
+    events: &'a [Event],
+    hosts: &'a [String],
+    host_events: HashMap<String, Vec<Event>>,
+    host: usize,
+    clock: usize,
+    offset: usize,
+    highlight: Highlight,
+    cut: Vec<usize>,
+}
+
+impl<'a> Browser<'a> {
+    fn new(events: &'a [Event], hosts: &'a [String]) -> Browser<'a> {
+        let mut host_events: HashMap<String, Vec<Event>> = hosts
+            .iter()
+            .map(|h| (h.clone(), Vec::<Event>::new()))
+            .collect();
+        events
+            .iter()
+            .for_each(|e| host_events.get_mut(&e.host).unwrap().push(e.clone()));
+        // start with the cut at the last event of every host
+        let cut = hosts.iter().map(|h| host_events[h].len()).collect();
+        Browser {
+            events,
+            hosts,
+            host_events,
+            host: 0,
+            clock: 1,
+            offset: 0,
+            highlight: Highlight::None,
+            cut,
+        }
+    }
+
+    fn host_len(&self, host: usize) -> usize {
+        self.host_events[&self.hosts[host]].len()
+    }
+
+    fn selected(&self) -> &Event {
+        &self.host_events[&self.hosts[self.host]][self.clock - 1]
+    }
+
+    fn select_host(&mut self, host: usize) {
+        self.host = host;
+        self.clock = self.clock.min(self.host_len(host)).max(1);
+    }
+
+    fn select(&mut self, host: &str, clock: usize) {
+        self.host = self.hosts.iter().position(|h| h.eq(host)).unwrap();
+        self.clock = clock;
+    }
+
+    /// Jumps from a receive to its send event or from a send to its receive event.
+    fn jump_to_match(&mut self) {
+        let selected = self.selected().clone();
+        if selected.title.eq(RECV_EVENT) {
+            let (host, clock) = selected.sender_clock.unwrap();
+            self.select(&host, clock);
+        } else if selected.title.eq(SEND_EVENT) {
+            let key = (selected.host.clone(), selected.clock);
+            if let Some(recv) = self
+                .events
+                .iter()
+                .find(|e| e.sender_clock.as_ref().is_some_and(|s| s.eq(&key)))
+            {
+                self.select(&recv.host.clone(), recv.clock);
+            }
+        }
+    }
+
+    fn cut_events(&self) -> Vec<Event> {
+        self.hosts
+            .iter()
+            .zip(self.cut.iter())
+            .map(|(h, clock)| self.host_events[h][clock - 1].clone())
+            .collect()
+    }
+
+    fn draw(&mut self, stdout: &mut Stdout) -> Result<(), Box<dyn Error>> {
+        let (width, _) = terminal::size()?;
+        let name_width = self.hosts.iter().map(|h| h.len()).max().unwrap() as u16 + 2;
+        let visible = ((width.saturating_sub(name_width)) / EVENT_WIDTH).max(1) as usize;
+
+        // scroll so the selected event stays visible
+        if self.clock - 1 < self.offset {
+            self.offset = self.clock - 1;
+        } else if self.clock > self.offset + visible {
+            self.offset = self.clock - visible;
+        }
+
+        stdout.queue(terminal::Clear(ClearType::All))?;
+        let selected = self.selected().clone();
+        let cut = self.cut_events();
+
+        for (y, host) in self.hosts.iter().enumerate() {
+            let y = 2 * y as u16;
+            stdout.queue(cursor::MoveTo(0, y))?;
+            write!(stdout, "{host}")?;
+            for (i, event) in self.host_events[host]
+                .iter()
+                .enumerate()
+                .skip(self.offset)
+                .take(visible)
+            {
+                let x = name_width + (i - self.offset) as u16 * EVENT_WIDTH;
+                stdout.queue(cursor::MoveTo(x, y))?;
+                let label = if event.title.eq(RECV_EVENT)
+                    | event.title.eq(SEND_EVENT)
+                    | event.title.eq(CHECKPOINT_EVENT)
+                    | event.title.eq(JOIN_EVENT)
+                    | event.title.eq(LEAVE_EVENT)
+                {
+                    event.title.chars().next().unwrap()
+                } else {
+                    'o'
+                };
+                let content = match self.highlight {
+                    Highlight::Causal if happened_before(event, &selected) => label.blue(),
+                    Highlight::Causal if happened_before(&selected, event) => label.green(),
+                    Highlight::Cut
+                        if cut
+                            .iter()
+                            .any(|e| e.host.eq(host) & (e.clock == event.clock)) =>
+                    {
+                        label.yellow()
+                    }
+                    _ => label.white(),
+                };
+                let content = if event.host.eq(&selected.host) & (event.clock == selected.clock) {
+                    content.reverse()
+                } else {
+                    content
+                };
+                stdout.queue(style::PrintStyledContent(content))?;
+                write!(stdout, "--")?;
+            }
+        }
+
+        let mut y = 2 * self.hosts.len() as u16;
+        let mut line = |stdout: &mut Stdout, text: String| -> io::Result<()> {
+            stdout.queue(cursor::MoveTo(0, y))?;
+            write!(stdout, "{text}")?;
+            y += 1;
+            Ok(())
+        };
+
+        line(
+            stdout,
+            format!(
+                "{} {} at clock {}",
+                selected.host, selected.title, selected.clock
+            ),
+        )?;
+        let vec_clock = self
+            .hosts
+            .iter()
+            .enumerate()
+            .map(|(i, h)| format!("{h}: {}", selected.vec_clock.get(i).unwrap_or(&0)))
+            .collect::<Vec<String>>()
+            .join(", ");
+        line(stdout, format!("vector clock {{{vec_clock}}}"))?;
+        if let Some((host, clock)) = selected.sender_clock.as_ref() {
+            line(stdout, format!("received from {host} at clock {clock}"))?;
+        }
+        match self.highlight {
+            Highlight::Causal => line(
+                stdout,
+                String::from("causal past blue, causal future green"),
+            )?,
+            Highlight::Cut => {
+                let consistent = is_consistent_cut(&cut, &self.host_events);
+                line(
+                    stdout,
+                    format!(
+                        "cut {:?} is {}",
+                        self.cut,
+                        if consistent {
+                            "consistent"
+                        } else {
+                            "inconsistent"
+                        }
+                    ),
+                )?
+            }
+            Highlight::None => {}
+        }
+        line(stdout, String::new())?;
+        line(
+            stdout,
+            String::from(
+                "arrows move, m jump to send/receive, c causal, k cut, space set cut, q quit",
+            ),
+        )?;
+
+        stdout.flush()?;
+        Ok(())
+    }
+
+    fn run(&mut self, stdout: &mut Stdout) -> Result<(), Box<dyn Error>> {
+        loop {
+            self.draw(stdout)?;
+            if let TermEvent::Key(key) = event::read()? {
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => break,
+                    KeyCode::Left => self.clock = (self.clock - 1).max(1),
+                    KeyCode::Right => self.clock = (self.clock + 1).min(self.host_len(self.host)),
+                    KeyCode::Up => self.select_host(self.host.saturating_sub(1)),
+                    KeyCode::Down => self.select_host((self.host + 1).min(self.hosts.len() - 1)),
+                    KeyCode::Char('m') => self.jump_to_match(),
+                    KeyCode::Char('c') => {
+                        self.highlight = if self.highlight == Highlight::Causal {
+                            Highlight::None
+                        } else {
+                            Highlight::Causal
+                        }
+                    }
+                    KeyCode::Char('k') => {
+                        self.highlight = if self.highlight == Highlight::Cut {
+                            Highlight::None
+                        } else {
+                            Highlight::Cut
+                        }
+                    }
+                    KeyCode::Char(' ') => self.cut[self.host] = self.clock,
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Raw mode and alternate screen, restored when dropped, so a panic or an early return
+/// does not leave the terminal unusable.
+struct Terminal;
+
+impl Terminal {
+    fn enter(stdout: &mut Stdout) -> io::Result<Terminal> {
+        enable_raw_mode()?;
+        let terminal = Terminal;
+        stdout.execute(EnterAlternateScreen)?;
+        stdout.execute(cursor::Hide)?;
+        Ok(terminal)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = disable_raw_mode();
+        let _ = stdout.execute(LeaveAlternateScreen);
+        let _ = stdout.execute(cursor::Show);
+    }
+}
+
+/// Interactive terminal browser for a trace with assigned vector clocks.
+pub fn run(events: &[Event], hosts: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut stdout = io::stdout();
+    let _terminal = Terminal::enter(&mut stdout)?;
+    Browser::new(events, hosts).run(&mut stdout)
+}
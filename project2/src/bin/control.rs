@@ -0,0 +1,56 @@
+use clap::{arg, command, Command};
+
+const QUERY_TIMEOUT: i32 = 1000;
+
+fn main() -> Result<(), zmq::Error> {
+    let matches = command!()
+        .about("Queries the membership table of a node or follows its membership changes")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("members")
+                .about("Prints the membership table as JSON")
+                .arg(arg!(<addr> "Control address of the node e.g. localhost:6550")),
+        )
+        .subcommand(
+            Command::new("watch")
+                .about("Prints membership change events as they happen")
+                .arg(arg!(<addr> "Event address of the node e.g. localhost:7550"))
+                .arg(arg!([events] ... "Only these events e.g. faulty removed")),
+        )
+        .get_matches();
+
+    let context = zmq::Context::new();
+    match matches.subcommand() {
+        Some(("members", matches)) => {
+            let addr = matches.get_one::<String>("addr").unwrap();
+            let requester = context.socket(zmq::REQ)?;
+            requester.set_rcvtimeo(QUERY_TIMEOUT)?;
+            requester.set_linger(0)?;
+            requester.connect(format!("tcp://{addr}").as_str())?;
+            requester.send("members", 0)?;
+            match requester.recv_string(0) {
+                Ok(Ok(reply)) => println!("{reply}"),
+                _ => eprintln!("node at {addr} did not answer"),
+            }
+        }
+        Some(("watch", matches)) => {
+            let addr = matches.get_one::<String>("addr").unwrap();
+            let subscriber = context.socket(zmq::SUB)?;
+            subscriber.connect(format!("tcp://{addr}").as_str())?;
+            match matches.get_many::<String>("events") {
+                Some(events) => events
+                    .into_iter()
+                    .try_for_each(|event| subscriber.set_subscribe(event.as_bytes()))?,
+                None => subscriber.set_subscribe(b"")?,
+            }
+            loop {
+                let frames = subscriber.recv_multipart(0)?;
+                if let Some(event) = frames.last() {
+                    println!("{}", String::from_utf8_lossy(event));
+                }
+            }
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
@@ -0,0 +1,508 @@
+use clap::{arg, command, value_parser};
+use plotters::prelude::*;
+use std::{
+    collections::BTreeMap,
+    env,
+    error::Error,
+    fs::{self, File},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+#[path = "../summary.rs"]
+mod summary;
+
+use summary::summary;
+
+const FONT_SIZE: u32 = 20;
+
+/// Parsed line of a node log.
+enum Entry {
+    Started(u64),
+    Send,
+    Terminated,
+    /// Node, state and generation it was seen with, 0 if unknown.
+    Transition(usize, &'static str, u64),
+}
+
+/// Node and generation, every start of a node process begins a new generation
+/// that runs until the process crashes or leaves.
+type Episode = (usize, u64);
+
+/// Everything observed during a run, times in seconds since start.
+#[derive(Default)]
+struct Run {
+    transitions: Vec<(f64, usize, Episode, &'static str)>,
+    sends: Vec<(f64, usize)>,
+    started: BTreeMap<Episode, f64>,
+    crashed: BTreeMap<Episode, f64>,
+}
+
+impl Run {
+    /// Generation node `i` runs or last ran with at `time`.
+    fn generation(&self, i: usize, time: f64) -> Option<u64> {
+        self.started
+            .iter()
+            .filter(|((j, _), at)| (*j == i) & (**at <= time))
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|((_, generation), _)| *generation)
+    }
+
+    /// Records the crash of the generation node `i` runs with.
+    fn crash(&mut self, i: usize, time: f64) {
+        if let Some(generation) = self.generation(i, time) {
+            self.crashed.entry((i, generation)).or_insert(time);
+        }
+    }
+
+    fn alive(&self, i: usize, time: f64) -> bool {
+        self.generation(i, time)
+            .is_some_and(|generation| self.crashed.get(&(i, generation)).is_none_or(|c| *c > time))
+    }
+
+    /// Starts of all generations but the first of every node.
+    fn restarted(&self) -> impl Iterator<Item = (&Episode, &f64)> {
+        self.started.iter().filter(|((i, generation), _)| {
+            self.started
+                .keys()
+                .any(|(j, g)| (j == i) & (g < generation))
+        })
+    }
+}
+
+fn parse(line: &str) -> Option<(usize, Entry)> {
+    let (id, text) = line.strip_prefix('[')?.split_once("] ")?;
+    let id = id.parse().ok()?;
+    if text.starts_with("Send ") {
+        return Some((id, Entry::Send));
+    }
+    if text.starts_with("Terminating") {
+        return Some((id, Entry::Terminated));
+    }
+    if let Some(generation) = text.strip_prefix("starting with generation ") {
+        return Some((id, Entry::Started(generation.parse().ok()?)));
+    }
+    let (node, rest) = text.strip_prefix("Node ")?.split_once(' ')?;
+    let state = match rest {
+        _ if rest.starts_with("set to faulty") => "faulty",
+        _ if rest.starts_with("removed") => "removed",
+        _ if rest.starts_with("suspected") => "suspect",
+        _ if rest.starts_with("left") => "left",
+        _ if rest.starts_with("recovered")
+            | rest.starts_with("restarted")
+            | rest.starts_with("rejoined")
+            | rest.starts_with("alive")
+            | rest.starts_with("learned") =>
+        {
+            "running"
+        }
+        _ => return None,
+    };
+    let generation = rest
+        .split_once("generation ")
+        .and_then(|(_, generation)| generation.split_whitespace().next()?.parse().ok())
+        .unwrap_or(0);
+    Some((id, Entry::Transition(node.parse().ok()?, state, generation)))
+}
+
+/// Sources the binary `bin` was built from, listed by cargo in the dep-info file next to it,
+/// spaces in paths are escaped with a backslash.
+fn sources(bin: &Path) -> Vec<PathBuf> {
+    let Ok(info) = fs::read_to_string(bin.with_extension("d")) else {
+        return Vec::new();
+    };
+    let Some((_, deps)) = info.lines().next().and_then(|l| l.split_once(": ")) else {
+        return Vec::new();
+    };
+    let mut sources = vec![String::new()];
+    let mut escaped = false;
+    for c in deps.chars() {
+        match (c, escaped) {
+            ('\\', false) => escaped = true,
+            (' ', false) => sources.push(String::new()),
+            _ => {
+                sources.last_mut().unwrap().push(c);
+                escaped = false;
+            }
+        }
+    }
+    sources
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Path of the node binary next to the harness, which has to be built after the last change
+/// of its sources, `cargo run --bin harness` only builds the harness.
+fn node_binary() -> Result<PathBuf, String> {
+    let harness = env::current_exe().map_err(|e| format!("failed locating harness: {e}"))?;
+    let bin = harness.with_file_name("project2");
+    let built = fs::metadata(&bin).and_then(|m| m.modified()).map_err(|_| {
+        format!(
+            "node binary {} not found, run cargo build --release first",
+            bin.display()
+        )
+    })?;
+    let changed = sources(&bin)
+        .iter()
+        .filter_map(|source| fs::metadata(source).and_then(|m| m.modified()).ok())
+        .max();
+    match changed {
+        Some(changed) if changed > built => Err(format!(
+            "node binary {} is older than its sources, run cargo build --release first",
+            bin.display()
+        )),
+        _ => Ok(bin),
+    }
+}
+
+/// Starts node `id`, collects its log into `run` and writes it to a file,
+/// the log of a restarted node is appended.
+fn spawn(
+    bin: &Path,
+    id: usize,
+    num: usize,
+    args: &[String],
+    start: Instant,
+    run: &Arc<Mutex<Run>>,
+    out: &str,
+) -> Child {
+    let mut child = Command::new(bin)
+        .arg(num.to_string())
+        .arg(id.to_string())
+        .args(args)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed starting node");
+    let stdout = child.stdout.take().unwrap();
+    let mut file = File::options()
+        .create(true)
+        .append(true)
+        .open(format!("{out}/node_{id}.log"))
+        .expect("failed opening log");
+    let run = Arc::clone(run);
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            let time = start.elapsed().as_secs_f64();
+            writeln!(file, "{time:.3} {line}").unwrap();
+            let mut run = run.lock().unwrap();
+            match parse(&line) {
+                Some((i, Entry::Started(generation))) => {
+                    run.started.insert((i, generation), time);
+                }
+                Some((i, Entry::Send)) => run.sends.push((time, i)),
+                Some((i, Entry::Terminated)) => run.crash(i, time),
+                Some((i, Entry::Transition(j, state, generation))) => {
+                    run.transitions.push((time, i, (j, generation), state))
+                }
+                None => {}
+            }
+        }
+    });
+    child
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let matches = command!()
+        .about("Launches nodes, crashes them on schedule and measures the failure detector")
+        .arg(arg!(<num> "Number of nodes").value_parser(value_parser!(usize)))
+        .arg(arg!(--crash <CRASHES> "Comma separated node@secs to kill e.g. 2@10,5@20"))
+        .arg(arg!(--duration <SECS> "Duration of the run").value_parser(value_parser!(f64)))
+        .arg(
+            arg!(--restart <SECS> "Restart crashed nodes after SECS")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(arg!(--out <DIR> "Directory for logs, CSV files and plots"))
+        .arg(arg!([args] ... "Arguments passed to every node").last(true))
+        .get_matches();
+    let num = *matches.get_one::<usize>("num").unwrap();
+    let duration = *matches.get_one::<f64>("duration").unwrap_or(&60.0);
+    let restart = matches.get_one::<f64>("restart").cloned();
+    let out = matches
+        .get_one::<String>("out")
+        .cloned()
+        .unwrap_or("results".to_owned());
+    let mut crashes: Vec<(usize, f64)> = matches
+        .get_one::<String>("crash")
+        .map(|crashes| {
+            crashes
+                .split(',')
+                .map(|crash| {
+                    let (node, at) = crash.split_once('@').expect("expected node@secs");
+                    (
+                        node.parse().expect("invalid node id"),
+                        at.parse().expect("invalid crash time"),
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    crashes.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let mut args: Vec<String> = matches
+        .get_many::<String>("args")
+        .map(|args| args.cloned().collect())
+        .unwrap_or_default();
+    // only scheduled crashes unless asked otherwise
+    if !args.iter().any(|a| a.eq("--crash")) {
+        args.extend(["--crash".to_owned(), "none".to_owned()]);
+    }
+    let bin = node_binary()?;
+    fs::create_dir_all(&out)?;
+    for i in 0..num {
+        File::create(format!("{out}/node_{i}.log"))?;
+    }
+
+    let start = Instant::now();
+    let run = Arc::new(Mutex::new(Run::default()));
+    let mut children: Vec<Child> = (0..num)
+        .map(|i| spawn(&bin, i, num, &args, start, &run, &out))
+        .collect();
+
+    // kill nodes on schedule and restart crashed ones
+    let mut alive = vec![true; num];
+    let mut restarts: Vec<(f64, usize)> = Vec::new();
+    let mut crashes = crashes.into_iter().peekable();
+    while start.elapsed().as_secs_f64() < duration {
+        let now = start.elapsed().as_secs_f64();
+        while let Some((node, _)) = crashes.next_if(|(_, at)| *at <= now) {
+            children[node].kill()?;
+            println!("Crashed node {node} at {now:.3}s");
+            run.lock().unwrap().crash(node, now);
+        }
+        for (i, child) in children.iter_mut().enumerate() {
+            if alive[i] & child.try_wait()?.is_some() {
+                alive[i] = false;
+                run.lock().unwrap().crash(i, now);
+                if let Some(restart) = restart {
+                    restarts.push((now + restart, i));
+                }
+            }
+        }
+        for (_, i) in restarts.iter().filter(|(at, _)| *at <= now) {
+            println!("Restarted node {i} at {now:.3}s");
+            children[*i] = spawn(&bin, *i, num, &args, start, &run, &out);
+            alive[*i] = true;
+        }
+        restarts.retain(|(at, _)| *at > now);
+        thread::sleep(Duration::from_millis(100));
+    }
+    for child in children.iter_mut() {
+        child.kill()?;
+        child.wait()?;
+    }
+    // let the readers drain the pipes
+    thread::sleep(Duration::from_millis(100));
+
+    let run = run.lock().unwrap();
+    report(num, duration, &run, &out)
+}
+
+fn report(num: usize, duration: f64, run: &Run, out: &str) -> Result<(), Box<dyn Error>> {
+    let mut transitions = File::create(format!("{out}/transitions.csv"))?;
+    writeln!(transitions, "time,observer,node,state")?;
+    for (time, i, (j, _), state) in run.transitions.iter() {
+        writeln!(transitions, "{time:.3},{i},{j},{state}")?;
+    }
+
+    // a transition belongs to the generation the observer saw the node with, the one
+    // running at the time if unknown, and is a false positive only if it was still running
+    let restarted: BTreeMap<Episode, f64> = run.restarted().map(|(e, at)| (*e, *at)).collect();
+    let mut detected: BTreeMap<(Episode, usize), f64> = BTreeMap::new();
+    let mut removed: BTreeMap<(Episode, usize), f64> = BTreeMap::new();
+    let mut false_positives = 0;
+    let mut recovered: BTreeMap<(Episode, usize), f64> = BTreeMap::new();
+    for (time, i, (j, generation), state) in run.transitions.iter() {
+        let generation = match generation {
+            0 => run.generation(*j, *time),
+            generation => Some(*generation),
+        };
+        let Some(generation) = generation else {
+            continue;
+        };
+        let episode = (*j, generation);
+        let key = (episode, *i);
+        let crashed = run.crashed.get(&episode).filter(|at| time >= at);
+        match (*state, crashed) {
+            ("faulty", Some(at)) => {
+                detected.entry(key).or_insert(time - at);
+            }
+            ("faulty", None) => false_positives += 1,
+            // a graceful leave is detected and removed at once
+            ("left", Some(at)) => {
+                detected.entry(key).or_insert(time - at);
+                removed.entry(key).or_insert(time - at);
+            }
+            ("removed", Some(at)) => {
+                removed.entry(key).or_insert(time - at);
+            }
+            ("running", _) => {
+                if let Some(at) = restarted.get(&episode) {
+                    recovered.entry(key).or_insert(time - at);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let of = |list: &BTreeMap<(Episode, usize), f64>, episode: &Episode| -> Vec<f64> {
+        list.iter()
+            .filter(|((e, _), _)| e == episode)
+            .map(|(_, d)| *d)
+            .collect()
+    };
+    let mut detection = File::create(format!("{out}/detection.csv"))?;
+    writeln!(detection, "node,crash,observer,detection,removal")?;
+    for (episode, at) in run.crashed.iter() {
+        let j = episode.0;
+        // nodes still running at the crash are expected to notice it
+        let observers: Vec<usize> = (0..num)
+            .filter(|i| (*i != j) & run.alive(*i, *at))
+            .collect();
+        for i in observers.iter() {
+            let removal = removed.get(&(*episode, *i)).map(|r| format!("{r:.3}"));
+            if let Some(d) = detected.get(&(*episode, *i)) {
+                writeln!(
+                    detection,
+                    "{j},{at:.3},{i},{d:.3},{}",
+                    removal.unwrap_or_default()
+                )?;
+            }
+        }
+        let latencies = of(&detected, episode);
+        let removals = of(&removed, episode);
+        let full = match removals.len() == observers.len() {
+            true => format!(
+                ", fully removed after {:.3}s",
+                removals.iter().cloned().fold(0.0, f64::max)
+            ),
+            false => String::new(),
+        };
+        println!(
+            "Node {j} crashed at {at:.3}s, detected by {} of {} nodes{}, removed by {}{full}",
+            latencies.len(),
+            observers.len(),
+            summary(&latencies),
+            removals.len(),
+        );
+    }
+    for (episode, at) in restarted.iter() {
+        let latencies = of(&recovered, episode);
+        println!(
+            "Node {} restarted at {at:.3}s, running again on {} nodes{}",
+            episode.0,
+            latencies.len(),
+            summary(&latencies)
+        );
+    }
+    println!("False positives {false_positives}");
+
+    let mut messages = vec![0usize; duration.ceil() as usize];
+    for (time, _) in run.sends.iter() {
+        if let Some(sent) = messages.get_mut(*time as usize) {
+            *sent += 1;
+        }
+    }
+    let mut csv = File::create(format!("{out}/messages.csv"))?;
+    writeln!(csv, "second,sent")?;
+    for (second, sent) in messages.iter().enumerate() {
+        writeln!(csv, "{second},{sent}")?;
+    }
+    println!(
+        "Message overhead {} messages, {:.2} per node and second",
+        run.sends.len(),
+        run.sends.len() as f64 / (num as f64 * duration)
+    );
+
+    draw_detection(num, &detected, &removed, out)?;
+    draw_messages(&messages, out)
+}
+
+fn draw_detection(
+    num: usize,
+    detected: &BTreeMap<(Episode, usize), f64>,
+    removed: &BTreeMap<(Episode, usize), f64>,
+    out: &str,
+) -> Result<(), Box<dyn Error>> {
+    let path = format!("{out}/detection.png");
+    let root = BitMapBackend::new(&path, (1024, 768)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let max = detected
+        .values()
+        .chain(removed.values())
+        .cloned()
+        .fold(1.0, f64::max);
+    let mut chart = ChartBuilder::on(&root)
+        .caption(
+            "Time from crash to faulty and removed",
+            ("sans-serif", FONT_SIZE),
+        )
+        .margin(20)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(-0.5..num as f64 - 0.5, 0.0..max * 1.1)?;
+    chart
+        .configure_mesh()
+        .x_desc("crashed node")
+        .y_desc("seconds")
+        .x_labels(num)
+        .x_label_formatter(&|x| format!("{x:.0}"))
+        .label_style(("sans-serif", FONT_SIZE))
+        .draw()?;
+
+    chart
+        .draw_series(
+            detected
+                .iter()
+                .map(|(((j, _), _), d)| Circle::new((*j as f64 - 0.1, *d), 5, RED.filled())),
+        )?
+        .label("faulty")
+        .legend(|(x, y)| Circle::new((x, y), 5, RED.filled()));
+    chart
+        .draw_series(
+            removed
+                .iter()
+                .map(|(((j, _), _), d)| Circle::new((*j as f64 + 0.1, *d), 5, BLUE.filled())),
+        )?
+        .label("removed")
+        .legend(|(x, y)| Circle::new((x, y), 5, BLUE.filled()));
+    chart
+        .configure_series_labels()
+        .background_style(WHITE)
+        .border_style(BLACK)
+        .label_font(("sans-serif", FONT_SIZE))
+        .draw()?;
+
+    root.present()?;
+    Ok(())
+}
+
+fn draw_messages(messages: &[usize], out: &str) -> Result<(), Box<dyn Error>> {
+    let path = format!("{out}/messages.png");
+    let root = BitMapBackend::new(&path, (1024, 768)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let max = *messages.iter().max().unwrap_or(&0).max(&1);
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Messages sent per second", ("sans-serif", FONT_SIZE))
+        .margin(20)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(0..messages.len(), 0..max + max / 10 + 1)?;
+    chart
+        .configure_mesh()
+        .x_desc("seconds")
+        .y_desc("messages")
+        .label_style(("sans-serif", FONT_SIZE))
+        .draw()?;
+    chart.draw_series(LineSeries::new(
+        messages.iter().enumerate().map(|(s, m)| (s, *m)),
+        &BLACK,
+    ))?;
+
+    root.present()?;
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use clap::{arg, command};
+use std::{
+    collections::{BTreeMap, HashSet, VecDeque},
+    error::Error,
+    fs::read_to_string,
+};
+
+// only the event captions are used, the trace writer belongs to the nodes
+#[allow(dead_code)]
+#[path = "../shiviz.rs"]
+mod shiviz;
+
+use shiviz::{RECV_EVENT, SEND_EVENT};
+
+/// Event of a node log as caption, host and the written clock entries.
+struct Event {
+    caption: String,
+    host: String,
+    clock: BTreeMap<String, usize>,
+    line: String,
+}
+
+impl Event {
+    /// Host and clock of the matching send of a receive.
+    fn sender(&self) -> Option<(String, usize)> {
+        self.clock
+            .iter()
+            .find(|(host, _)| !host.eq(&&self.host))
+            .map(|(host, c)| (host.clone(), *c))
+    }
+}
+
+fn parse(data: &str) -> VecDeque<Event> {
+    let lines: Vec<&str> = data.lines().collect();
+    lines
+        .chunks_exact(2)
+        .map(|e| {
+            let (host, clock) = e[1].split_once(' ').expect("invalid event");
+            Event {
+                caption: e[0].to_owned(),
+                host: host.to_owned(),
+                clock: serde_json::from_str(clock).expect("invalid clock"),
+                line: e[1].to_owned(),
+            }
+        })
+        .collect()
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let matches = command!()
+        .about("Merges the ShiViz logs of all nodes in causal order for the project1 analyser")
+        .arg(arg!(<logs> ... "Node logs written with --shiviz"))
+        .get_matches();
+    let mut logs: Vec<VecDeque<Event>> = matches
+        .get_many::<String>("logs")
+        .unwrap()
+        .map(|path| read_to_string(path).map(|data| parse(&data)))
+        .collect::<Result<_, _>>()?;
+
+    // take turns between the nodes, a receive waits until its send was written
+    let mut sent: HashSet<(String, usize)> = HashSet::new();
+    let mut next = 0;
+    while logs.iter().any(|log| !log.is_empty()) {
+        let ready = (0..logs.len()).map(|i| (next + i) % logs.len()).find(|i| {
+            logs[*i].front().is_some_and(|event| {
+                !event.caption.eq(RECV_EVENT)
+                    | event.sender().is_some_and(|send| sent.contains(&send))
+            })
+        });
+        let i = match ready {
+            Some(i) => i,
+            None => {
+                // the send was never written, e.g. the sender was killed while writing
+                let i = logs.iter().position(|log| !log.is_empty()).unwrap();
+                let event = logs[i].pop_front().unwrap();
+                eprintln!("Skipped receive without send {}", event.line);
+                continue;
+            }
+        };
+        let event = logs[i].pop_front().unwrap();
+        if event.caption.eq(SEND_EVENT) {
+            sent.insert((event.host.clone(), event.clock[&event.host]));
+        }
+        println!("{}\n{}", event.caption, event.line);
+        next = i + 1;
+    }
+    Ok(())
+}
@@ -0,0 +1,220 @@
+use serde::Deserialize;
+use std::{fs::read_to_string, str::FromStr, time::Duration};
+
+use crate::faults::Faults;
+
+const T_GOSSIP: f64 = 2.0;
+const T_FAIL: f64 = 10.0;
+const T_CLEANUP: f64 = 20.0;
+const T_PING: f64 = 1.0;
+const INDIRECT: usize = 3;
+const PHI_FAIL: f64 = 8.0;
+const PHI_CLEANUP: f64 = 16.0;
+const CRASH_RATE: f64 = 0.1;
+const FANOUT: usize = 1;
+const SHUFFLE: usize = 4;
+const BASE_PORT: usize = 5550;
+const CONTROL_PORT_OFFSET: usize = 1000;
+const EVENTS_PORT_OFFSET: usize = 2000;
+
+/// Crash schedule of a node, written as `none`, `random:<prob>` or `at:<secs>`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Crash {
+    None,
+    /// Terminates with the given probability after every gossip round.
+    Random(f64),
+    /// Terminates after the given number of seconds.
+    At(f64),
+}
+
+impl Crash {
+    pub fn due<R: rand::Rng>(&self, rng: &mut R, now: Duration) -> bool {
+        match self {
+            Crash::None => false,
+            Crash::Random(prob) => rng.gen::<f64>() < *prob,
+            Crash::At(secs) => now.as_secs_f64() >= *secs,
+        }
+    }
+}
+
+impl FromStr for Crash {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = |v: &str| v.parse::<f64>().map_err(|e| format!("{s}: {e}"));
+        match s.split_once(':') {
+            None if s.eq("none") => Ok(Crash::None),
+            Some(("random", prob)) => Ok(Crash::Random(value(prob)?)),
+            Some(("at", secs)) => Ok(Crash::At(value(secs)?)),
+            _ => Err(format!("{s}: expected none, random:<prob> or at:<secs>")),
+        }
+    }
+}
+
+impl TryFrom<String> for Crash {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Transport the nodes exchange messages over, written as `zmq`, `udp` or `memory`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Zmq,
+    Udp,
+    /// All nodes run as threads of one process.
+    Memory,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zmq" => Ok(Backend::Zmq),
+            "udp" => Ok(Backend::Udp),
+            "memory" => Ok(Backend::Memory),
+            _ => Err(format!("{s}: expected zmq, udp or memory")),
+        }
+    }
+}
+
+/// Node configuration, timers are given in seconds.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub t_gossip: f64,
+    pub t_fail: f64,
+    pub t_cleanup: f64,
+    /// Receivers per gossip round in heartbeat gossip.
+    pub fanout: usize,
+    /// Receivers of heartbeat gossip reply with the entries the sender is missing or has stale.
+    pub push_pull: bool,
+    /// Size of the partial view heartbeat gossip is restricted to, 0 keeps the full membership.
+    pub view: usize,
+    /// View entries exchanged per shuffle.
+    pub shuffle: usize,
+    /// Use SWIM probing instead of heartbeat gossip, T_GOSSIP is the protocol period
+    /// and T_FAIL the suspicion timeout.
+    pub swim: bool,
+    /// Time to wait for an ack before asking other nodes to probe.
+    pub t_ping: f64,
+    /// Number of nodes asked to probe indirectly.
+    pub indirect: usize,
+    /// Use the phi accrual detector instead of T_FAIL and T_CLEANUP in heartbeat gossip.
+    pub phi: bool,
+    pub phi_fail: f64,
+    pub phi_cleanup: f64,
+    /// When the node terminates on its own.
+    pub crash: Crash,
+    /// Announce a graceful leave when terminating, otherwise peers have to detect it as a crash.
+    pub leave: bool,
+    pub transport: Backend,
+    /// Address the inbox binds to, defaults to all interfaces on the port of the own peer entry.
+    pub bind: Option<String>,
+    /// host:port of every node, indexed by node id.
+    pub peers: Vec<String>,
+    /// host:port other nodes use to reach this node, defaults to the own peer entry.
+    pub addr: Option<String>,
+    /// Directory the ShiViz log of the gossip messages is written to.
+    pub shiviz: Option<String>,
+    /// Serve membership queries and change events on localhost.
+    pub control: bool,
+    /// host:port of nodes to ask for the membership list when joining.
+    pub seeds: Vec<String>,
+    /// Faults injected into the network, none by default.
+    pub faults: Faults,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            t_gossip: T_GOSSIP,
+            t_fail: T_FAIL,
+            t_cleanup: T_CLEANUP,
+            fanout: FANOUT,
+            push_pull: false,
+            view: 0,
+            shuffle: SHUFFLE,
+            swim: false,
+            t_ping: T_PING,
+            indirect: INDIRECT,
+            phi: false,
+            phi_fail: PHI_FAIL,
+            phi_cleanup: PHI_CLEANUP,
+            crash: Crash::Random(CRASH_RATE),
+            leave: true,
+            transport: Backend::Zmq,
+            bind: None,
+            peers: Vec::new(),
+            addr: None,
+            shiviz: None,
+            control: false,
+            seeds: Vec::new(),
+            faults: Faults::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &str) -> Config {
+        let data = read_to_string(path).expect("failed reading config file");
+        toml::from_str(&data).expect("invalid config file")
+    }
+
+    pub fn t_gossip(&self) -> Duration {
+        Duration::from_secs_f64(self.t_gossip)
+    }
+
+    pub fn t_fail(&self) -> Duration {
+        Duration::from_secs_f64(self.t_fail)
+    }
+
+    pub fn t_cleanup(&self) -> Duration {
+        Duration::from_secs_f64(self.t_cleanup)
+    }
+
+    pub fn t_ping(&self) -> Duration {
+        Duration::from_secs_f64(self.t_ping)
+    }
+
+    /// Address of node `id`, peers default to localhost with consecutive ports.
+    pub fn peer(&self, id: usize) -> String {
+        self.peers
+            .get(id)
+            .cloned()
+            .unwrap_or_else(|| format!("localhost:{}", BASE_PORT + id))
+    }
+
+    pub fn addr(&self, id: usize) -> String {
+        self.addr.clone().unwrap_or_else(|| self.peer(id))
+    }
+
+    pub fn bind(&self, id: usize) -> String {
+        self.bind.clone().unwrap_or_else(|| {
+            let (_, port) = split_port(&self.addr(id));
+            format!("tcp://*:{port}")
+        })
+    }
+
+    /// Membership queries are answered on the inbox port + CONTROL_PORT_OFFSET.
+    pub fn control_bind(&self, id: usize) -> String {
+        let (_, port) = split_port(&self.addr(id));
+        format!("tcp://127.0.0.1:{}", port + CONTROL_PORT_OFFSET)
+    }
+
+    /// Membership changes are published on the inbox port + EVENTS_PORT_OFFSET.
+    pub fn events_bind(&self, id: usize) -> String {
+        let (_, port) = split_port(&self.addr(id));
+        format!("tcp://127.0.0.1:{}", port + EVENTS_PORT_OFFSET)
+    }
+}
+
+fn split_port(addr: &str) -> (&str, usize) {
+    let (host, port) = addr.rsplit_once(':').expect("address without port");
+    (host, port.parse().expect("invalid port"))
+}
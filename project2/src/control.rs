@@ -0,0 +1,119 @@
+use serde::Serialize;
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::{
+    log,
+    membership::{Detector, State},
+};
+
+/// Membership change published to subscribers, the event name is also the topic frame.
+#[derive(Debug, Serialize)]
+struct Event {
+    time: f64,
+    node: usize,
+    event: &'static str,
+    state: State,
+}
+
+/// Local control endpoint of a node, a REP socket answers `members` with the membership table
+/// as JSON and a PUB socket streams membership changes.
+pub struct Control {
+    id: usize,
+    queries: zmq::Socket,
+    events: zmq::Socket,
+    /// Last state of every node, to tell joins, recoveries and rejoins apart.
+    known: BTreeMap<usize, State>,
+}
+
+impl Control {
+    pub fn bind<D: Detector>(
+        id: usize,
+        queries: &str,
+        events: &str,
+        detector: &D,
+        now: Duration,
+    ) -> Control {
+        let context = zmq::Context::new();
+        let query_socket = context.socket(zmq::REP).unwrap();
+        query_socket
+            .bind(queries)
+            .expect("failed binding control queries");
+        let event_socket = context.socket(zmq::PUB).unwrap();
+        event_socket
+            .bind(events)
+            .expect("failed binding control events");
+        Control {
+            id,
+            queries: query_socket,
+            events: event_socket,
+            known: detector
+                .table(now)
+                .into_iter()
+                .map(|(i, status)| (i, status.state))
+                .collect(),
+        }
+    }
+
+    /// Answers all pending queries without blocking, every request gets a reply so the
+    /// socket is ready for the next one. Socket errors are logged.
+    pub fn serve<D: Detector>(&self, detector: &D, now: Duration) {
+        loop {
+            let request = match self.queries.recv_bytes(zmq::DONTWAIT) {
+                Ok(request) => request,
+                Err(zmq::Error::EAGAIN) => return,
+                Err(e) => {
+                    log(
+                        self.id,
+                        format!("Failed receiving control query: {e}").as_str(),
+                    );
+                    return;
+                }
+            };
+            let reply = match std::str::from_utf8(&request).map(str::trim) {
+                Ok("members") => serde_json::to_string(&detector.table(now)).unwrap(),
+                Ok(request) => {
+                    format!("{{\"error\":\"unknown request {request:?}, expected members\"}}")
+                }
+                Err(_) => "{\"error\":\"request is not UTF-8, expected members\"}".to_owned(),
+            };
+            if let Err(e) = self.queries.send(reply.as_str(), 0) {
+                log(
+                    self.id,
+                    format!("Failed sending control reply: {e}").as_str(),
+                );
+                return;
+            }
+        }
+    }
+
+    /// Publishes state changes as joined, recovered, rejoined, suspected, faulty, removed
+    /// or left events.
+    pub fn publish(&mut self, changes: &[(usize, State)], now: Duration) {
+        for (node, state) in changes {
+            let previous = self.known.insert(*node, state.clone());
+            let event = match (state, previous) {
+                (State::Running, None) => "joined",
+                (State::Running, Some(State::Suspect | State::Faulty)) => "recovered",
+                (State::Running, Some(State::Removed | State::Left)) => "rejoined",
+                (State::Running, Some(State::Running)) => continue,
+                (State::Suspect, _) => "suspected",
+                (State::Faulty, _) => "faulty",
+                (State::Removed, _) => "removed",
+                (State::Left, _) => "left",
+            };
+            let event = Event {
+                time: now.as_secs_f64(),
+                node: *node,
+                event,
+                state: state.clone(),
+            };
+            let frames = [event.event, &serde_json::to_string(&event).unwrap()];
+            if let Err(e) = self.events.send_multipart(frames, 0) {
+                log(
+                    self.id,
+                    format!("Failed publishing control event: {e}").as_str(),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use rand::{seq::IteratorRandom, Rng};
+
+/// Bounded random view of peers maintained by Cyclon shuffles (Voulgaris et al.), every entry
+/// carries the number of shuffles since it was created by its node.
+pub struct View {
+    size: usize,
+    /// Number of entries exchanged per shuffle.
+    shuffle: usize,
+    entries: Vec<(usize, u32)>,
+    /// Entries sent with the last shuffle, replaced by the entries of the reply.
+    sent: Vec<usize>,
+}
+
+impl View {
+    pub fn new(size: usize, shuffle: usize) -> View {
+        View {
+            size,
+            shuffle: shuffle.min(size),
+            entries: Vec::new(),
+            sent: Vec::new(),
+        }
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        self.entries.iter().any(|(j, _)| *j == i)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Fills the free slots with random candidates, used on start and when the view ran empty.
+    pub fn fill<R: Rng>(&mut self, candidates: impl Iterator<Item = usize>, rng: &mut R) {
+        let free = self.size - self.entries.len();
+        let new: Vec<usize> = candidates
+            .filter(|i| !self.contains(*i))
+            .choose_multiple(rng, free);
+        self.entries.extend(new.into_iter().map(|i| (i, 0)));
+    }
+
+    pub fn remove(&mut self, i: usize) {
+        self.entries.retain(|(j, _)| *j != i);
+    }
+
+    /// Ages all entries and removes the oldest peer, which gets the own entry with a random
+    /// sample of the remaining ones. Returns the peer and the sample.
+    pub fn start_shuffle<R: Rng>(
+        &mut self,
+        id: usize,
+        rng: &mut R,
+    ) -> Option<(usize, Vec<(usize, u32)>)> {
+        self.entries.iter_mut().for_each(|(_, age)| *age += 1);
+        let oldest = self
+            .entries
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (_, age))| *age)
+            .map(|(k, _)| k)?;
+        let (peer, _) = self.entries.swap_remove(oldest);
+        let mut sample = self
+            .entries
+            .iter()
+            .cloned()
+            .choose_multiple(rng, self.shuffle.saturating_sub(1));
+        self.sent = sample.iter().map(|(j, _)| *j).collect();
+        sample.push((id, 0));
+        Some((peer, sample))
+    }
+
+    /// Answers a shuffle of node `from` with a random sample of the own entries and merges
+    /// the received ones in place of the sample. Returns the sample and the added peers.
+    pub fn answer<R: Rng>(
+        &mut self,
+        id: usize,
+        from: usize,
+        received: &[(usize, u32)],
+        rng: &mut R,
+    ) -> (Vec<(usize, u32)>, Vec<usize>) {
+        let sample = self
+            .entries
+            .iter()
+            .filter(|(j, _)| *j != from)
+            .cloned()
+            .choose_multiple(rng, self.shuffle);
+        let mut sent = sample.iter().map(|(j, _)| *j).collect();
+        let added = self.merge(id, received, &mut sent);
+        (sample, added)
+    }
+
+    /// Merges the reply to the last shuffle in place of the entries sent with it.
+    pub fn reply(&mut self, id: usize, received: &[(usize, u32)]) -> Vec<usize> {
+        let mut sent = std::mem::take(&mut self.sent);
+        self.merge(id, received, &mut sent)
+    }
+
+    /// Adds received entries that are neither the own node nor already known, into free slots
+    /// first and then in place of the `sent` entries. Returns the added peers.
+    fn merge(&mut self, id: usize, received: &[(usize, u32)], sent: &mut Vec<usize>) -> Vec<usize> {
+        let mut added = Vec::new();
+        for (j, age) in received {
+            if (*j == id) | self.contains(*j) {
+                continue;
+            }
+            if self.entries.len() >= self.size {
+                while let Some(replaced) = sent.pop() {
+                    if self.contains(replaced) {
+                        self.remove(replaced);
+                        break;
+                    }
+                }
+                if self.entries.len() >= self.size {
+                    break;
+                }
+            }
+            self.entries.push((*j, *age));
+            added.push(*j);
+        }
+        added
+    }
+}
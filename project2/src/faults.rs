@@ -0,0 +1,92 @@
+use rand::Rng;
+use serde::Deserialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Drop probability of a single directed link, overrides the global loss.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Link {
+    pub from: usize,
+    pub to: usize,
+    pub loss: f64,
+}
+
+/// Nodes in different groups can not reach each other between `start` and `end` seconds,
+/// nodes in no group are not affected.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Partition {
+    pub start: f64,
+    pub end: f64,
+    pub groups: Vec<Vec<usize>>,
+}
+
+/// Network faults injected on the receiving side, delays are given in seconds.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Faults {
+    pub loss: f64,
+    pub delay: f64,
+    pub jitter: f64,
+    pub duplicate: f64,
+    pub links: Vec<Link>,
+    pub partitions: Vec<Partition>,
+    /// Unix time in seconds the partition windows of real nodes are measured from,
+    /// otherwise every node measures them from its own start.
+    pub epoch: Option<f64>,
+}
+
+impl Faults {
+    fn loss(&self, from: usize, to: usize) -> f64 {
+        self.links
+            .iter()
+            .find(|l| (l.from == from) & (l.to == to))
+            .map_or(self.loss, |l| l.loss)
+    }
+
+    fn partitioned(&self, from: usize, to: usize, now: Duration) -> bool {
+        let now = now.as_secs_f64();
+        self.partitions
+            .iter()
+            .filter(|p| (p.start <= now) & (now < p.end))
+            .any(|p| {
+                let group = |i: usize| p.groups.iter().position(|g| g.contains(&i));
+                matches!((group(from), group(to)), (Some(a), Some(b)) if a != b)
+            })
+    }
+
+    /// Time partition windows are checked at, the time since the epoch if set,
+    /// otherwise the time `elapsed` since the node started.
+    pub fn time(&self, elapsed: Duration) -> Duration {
+        match self.epoch {
+            Some(epoch) => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .saturating_sub(Duration::from_secs_f64(epoch)),
+            None => elapsed,
+        }
+    }
+
+    /// Returns the delay of every copy of a message on the link `from` -> `to`,
+    /// no delay at all means the message is lost.
+    pub fn deliveries<R: Rng>(
+        &self,
+        rng: &mut R,
+        from: usize,
+        to: usize,
+        now: Duration,
+    ) -> Vec<Duration> {
+        if self.partitioned(from, to, now) | (rng.gen::<f64>() < self.loss(from, to)) {
+            return Vec::new();
+        }
+        let copies = if rng.gen::<f64>() < self.duplicate {
+            2
+        } else {
+            1
+        };
+        (0..copies)
+            .map(|_| {
+                let jitter = self.jitter * rng.gen_range(-1.0..=1.0);
+                Duration::from_secs_f64((self.delay + jitter).max(0.0))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    log,
+    membership::{Detector, Version},
+    transport::Transport,
+    wire::{self, Tag},
+};
+
+const JOIN_TIMEOUT: Duration = Duration::from_secs(1);
+/// Rounds over all seeds before giving up.
+const JOIN_ROUNDS: usize = 3;
+/// Seeds are only known by address.
+const UNKNOWN_ID: usize = usize::MAX;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Join {
+    id: usize,
+    addr: String,
+}
+
+/// Membership list sent to a joining node.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Membership {
+    pub heartbeats: BTreeMap<usize, Version>,
+    pub peers: BTreeMap<usize, String>,
+}
+
+/// Answers a join request with the current membership list and returns the id of the new node,
+/// which is then disseminated to everyone else through gossip. Malformed requests are dropped,
+/// a reply that can not be sent is logged and the node learns the members through gossip.
+pub fn answer<D: Detector, T: Transport>(
+    id: usize,
+    request: &[u8],
+    detector: &mut D,
+    transport: &mut T,
+    now: Duration,
+) -> Option<usize> {
+    let join: Join = match wire::body(request) {
+        Ok(join) => join,
+        Err(e) => {
+            log(id, format!("Dropped join request: {e}").as_str());
+            return None;
+        }
+    };
+    log(
+        id,
+        format!("Node {} joined with address {}", join.id, join.addr).as_str(),
+    );
+    detector.add(join.id, join.addr.clone(), now);
+
+    let reply = Membership {
+        heartbeats: detector.heartbeats(),
+        peers: detector.peers(),
+    };
+    let reply = wire::encode(Tag::Members, id, &reply);
+    if let Err(e) = transport.send(join.id, &join.addr, &reply) {
+        log(
+            id,
+            format!("Failed answering join of node {}: {e}", join.id).as_str(),
+        );
+    }
+    Some(join.id)
+}
+
+/// Asks the seeds for the current membership list until one of them answers, seeds that can
+/// not be reached are retried in the next round. Other messages and malformed replies received
+/// meanwhile are dropped.
+pub fn join<T: Transport>(
+    id: usize,
+    addr: &str,
+    seeds: &[String],
+    transport: &mut T,
+) -> Option<Membership> {
+    let join = Join {
+        id,
+        addr: addr.to_owned(),
+    };
+    let request = wire::encode(Tag::Join, id, &join);
+    for seed in (0..JOIN_ROUNDS).flat_map(|_| seeds) {
+        if let Err(e) = transport.send(UNKNOWN_ID, seed, &request) {
+            log(
+                id,
+                format!("Failed sending join request to seed {seed}: {e}").as_str(),
+            );
+            thread::sleep(JOIN_TIMEOUT);
+            continue;
+        }
+        let deadline = Instant::now() + JOIN_TIMEOUT;
+        while let Some(wait) = deadline.checked_duration_since(Instant::now()) {
+            let Some(frame) = transport.recv(wait) else {
+                // the transport may give up before the timeout, wait out the round
+                thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                break;
+            };
+            let reply = wire::decode(&frame).and_then(|(tag, _, body)| match tag {
+                Tag::Members => wire::body(body).map(Some),
+                _ => Ok(None),
+            });
+            match reply {
+                Ok(Some(reply)) => {
+                    log(id, format!("Joined through seed {seed}").as_str());
+                    return Some(reply);
+                }
+                Ok(None) => {}
+                Err(e) => log(id, format!("Dropped frame: {e}").as_str()),
+            }
+        }
+        log(id, format!("Seed {seed} did not answer").as_str());
+    }
+    None
+}
@@ -1,210 +1,473 @@
-use clap::{arg, command};
-use rand::{seq::IteratorRandom, Rng};
-use serde::{Deserialize, Serialize};
+use clap::{arg, command, value_parser};
+use config::{Backend, Config, Crash};
+use control::Control;
+use membership::{Detector, Membership, Packet, State, T_CHECK};
+use serde::Serialize;
+use shiviz::{Stamped, Trace};
 use std::{
-    collections::{BTreeMap, HashMap},
-    sync::{Arc, Mutex},
+    collections::BTreeMap,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     thread,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use swim::Swim;
+use transport::{Transport, Udp, Zmq};
+use wire::Tag;
 
-const T_GOSSIP: u64 = 2;
-const T_FAIL: u64 = 10;
-const T_CLEANUP: u64 = 20;
+mod config;
+mod control;
+mod cyclon;
+mod faults;
+mod join;
+mod membership;
+mod phi;
+mod shiviz;
+mod sim;
+mod summary;
+mod swim;
+mod transport;
+mod wire;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Message {
-    msg_id: usize,
-    receiver_id: usize,
-    heartbeats: BTreeMap<usize, u64>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-enum State {
-    Running,
-    Faulty,
-    Removed,
-}
+/// Time the leave announcement gets to reach the other nodes.
+const LEAVE_FLUSH: Duration = Duration::from_millis(200);
 
-#[derive(Debug, Clone)]
-struct Node {
-    state: State,
-    updated: Instant,
-    heartbeat: u64,
-}
-
-impl Default for Node {
-    fn default() -> Self {
-        Self {
-            state: State::Running,
-            updated: Instant::now(),
-            heartbeat: 0,
-        }
-    }
-}
+/// Suppresses the node logs, set when simulating large clusters.
+static QUIET: AtomicBool = AtomicBool::new(false);
 
 fn log(id: usize, str: &str) {
-    println!("[{id}] {str}");
+    if !QUIET.load(Ordering::Relaxed) {
+        println!("[{id}] {str}");
+    }
 }
 
-fn main() -> Result<(), zmq::Error> {
+fn main() -> io::Result<()> {
     let matches = command!()
-        .arg(arg!(<num> "Number of nodes"))
-        .arg(arg!(<node> "Node id (0..n)"))
+        .arg(arg!(<num> "Number of initial nodes"))
+        .arg(arg!([node] "Node id, not needed with --simulate or --transport memory"))
+        .arg(arg!(-c --config <FILE> "Path to TOML config file"))
+        .arg(arg!(--gossip <SECS> "Gossip interval T_GOSSIP").value_parser(value_parser!(f64)))
+        .arg(
+            arg!(--fail <SECS> "Time until a node is faulty T_FAIL")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(
+            arg!(--cleanup <SECS> "Time until a faulty node is removed T_CLEANUP")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(
+            arg!(--transport <BACKEND> "Transport zmq, udp or memory to run all nodes in one process")
+                .value_parser(value_parser!(Backend)),
+        )
+        .arg(arg!(--bind <ADDR> "Inbox bind address e.g. tcp://*:5550"))
+        .arg(arg!(--peers <PEERS> "Comma separated host:port of every node ordered by id"))
+        .arg(arg!(--addr <ADDR> "host:port other nodes use to reach this node"))
+        .arg(arg!(--shiviz <DIR> "Write a ShiViz log of the gossip messages to DIR/node<id>.log"))
+        .arg(arg!(--control "Serve membership queries and change events on localhost"))
+        .arg(arg!(--seeds <SEEDS> "Comma separated host:port of nodes to join through"))
+        .arg(
+            arg!(--loss <PROB> "Probability of dropping a message")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(arg!(--delay <SECS> "Delay of every message").value_parser(value_parser!(f64)))
+        .arg(
+            arg!(--jitter <SECS> "Maximum random deviation from the delay")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(
+            arg!(--duplicate <PROB> "Probability of delivering a message twice")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(
+            arg!(--epoch <SECS> "Unix time partition windows are measured from, each node's own start by default")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(
+            arg!(--fanout <K> "Number of receivers per gossip round")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(arg!(--"push-pull" "Reply to gossip with the entries the sender is missing"))
+        .arg(
+            arg!(--view <SIZE> "Gossip heartbeats only within a partial view of SIZE peers")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            arg!(--shuffle <L> "Number of view entries exchanged per shuffle")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(arg!(--swim "Use SWIM probing instead of heartbeat gossip"))
+        .arg(
+            arg!(--ping <SECS> "SWIM time to wait for an ack before indirect probing")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(
+            arg!(--indirect <K> "SWIM number of nodes asked to probe indirectly")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(arg!(--phi "Use the phi accrual detector instead of T_FAIL and T_CLEANUP"))
+        .arg(
+            arg!(--"phi-fail" <PHI> "Phi threshold until a node is faulty")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(
+            arg!(--"phi-cleanup" <PHI> "Phi threshold until a node is removed")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(
+            arg!(--crash <SCHEDULE> "Crash schedule none, random:<prob> or at:<secs>")
+                .value_parser(value_parser!(Crash)),
+        )
+        .arg(arg!(--"no-leave" "Terminate silently instead of announcing a graceful leave"))
+        .arg(
+            arg!(--restart <SECS> "Restart crashed nodes after SECS in the simulation")
+                .value_parser(value_parser!(f64)),
+        )
+        .arg(arg!(--simulate "Simulate num nodes in one process with virtual time"))
+        .arg(
+            arg!(--scale <SIZES> "Comma separated cluster sizes to simulate instead of num, only prints a summary per size")
+                .value_delimiter(',')
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(arg!(--seed <SEED> "Random seed of the simulation").value_parser(value_parser!(u64)))
+        .arg(
+            arg!(--duration <SECS> "Virtual duration of the simulation")
+                .value_parser(value_parser!(f64)),
+        )
         .get_matches();
-    let id: usize = matches
-        .get_one::<String>("node")
-        .unwrap()
-        .parse()
-        .expect("invalid node id");
     let num: usize = matches
         .get_one::<String>("num")
         .unwrap()
         .parse()
         .expect("invalid number of nodes");
 
-    log(id, "starting");
+    // command line arguments override the config file
+    let mut config = matches
+        .get_one::<String>("config")
+        .map(|path| Config::load(path))
+        .unwrap_or_default();
+    if let Some(t_gossip) = matches.get_one::<f64>("gossip") {
+        config.t_gossip = *t_gossip;
+    }
+    if let Some(t_fail) = matches.get_one::<f64>("fail") {
+        config.t_fail = *t_fail;
+    }
+    if let Some(t_cleanup) = matches.get_one::<f64>("cleanup") {
+        config.t_cleanup = *t_cleanup;
+    }
+    if let Some(transport) = matches.get_one::<Backend>("transport") {
+        config.transport = *transport;
+    }
+    if let Some(bind) = matches.get_one::<String>("bind") {
+        config.bind = Some(bind.clone());
+    }
+    if let Some(peers) = matches.get_one::<String>("peers") {
+        config.peers = peers.split(',').map(String::from).collect();
+    }
+    if let Some(addr) = matches.get_one::<String>("addr") {
+        config.addr = Some(addr.clone());
+    }
+    if let Some(shiviz) = matches.get_one::<String>("shiviz") {
+        config.shiviz = Some(shiviz.clone());
+    }
+    if *matches.get_one::<bool>("control").unwrap_or(&false) {
+        config.control = true;
+    }
+    if let Some(seeds) = matches.get_one::<String>("seeds") {
+        config.seeds = seeds.split(',').map(String::from).collect();
+    }
+    if let Some(loss) = matches.get_one::<f64>("loss") {
+        config.faults.loss = *loss;
+    }
+    if let Some(delay) = matches.get_one::<f64>("delay") {
+        config.faults.delay = *delay;
+    }
+    if let Some(jitter) = matches.get_one::<f64>("jitter") {
+        config.faults.jitter = *jitter;
+    }
+    if let Some(duplicate) = matches.get_one::<f64>("duplicate") {
+        config.faults.duplicate = *duplicate;
+    }
+    if let Some(epoch) = matches.get_one::<f64>("epoch") {
+        config.faults.epoch = Some(*epoch);
+    }
+    if let Some(fanout) = matches.get_one::<usize>("fanout") {
+        config.fanout = *fanout;
+    }
+    if *matches.get_one::<bool>("push-pull").unwrap_or(&false) {
+        config.push_pull = true;
+    }
+    if let Some(view) = matches.get_one::<usize>("view") {
+        config.view = *view;
+    }
+    if let Some(shuffle) = matches.get_one::<usize>("shuffle") {
+        config.shuffle = *shuffle;
+    }
+    if *matches.get_one::<bool>("swim").unwrap_or(&false) {
+        config.swim = true;
+    }
+    if let Some(t_ping) = matches.get_one::<f64>("ping") {
+        config.t_ping = *t_ping;
+    }
+    if let Some(indirect) = matches.get_one::<usize>("indirect") {
+        config.indirect = *indirect;
+    }
+    if *matches.get_one::<bool>("phi").unwrap_or(&false) {
+        config.phi = true;
+    }
+    if let Some(phi_fail) = matches.get_one::<f64>("phi-fail") {
+        config.phi_fail = *phi_fail;
+    }
+    if let Some(phi_cleanup) = matches.get_one::<f64>("phi-cleanup") {
+        config.phi_cleanup = *phi_cleanup;
+    }
+    if let Some(crash) = matches.get_one::<Crash>("crash") {
+        config.crash = *crash;
+    }
+    if *matches.get_one::<bool>("no-leave").unwrap_or(&false) {
+        config.leave = false;
+    }
+    assert!(
+        config.peers.is_empty() | (config.peers.len() == num),
+        "expected {num} peers but got {}",
+        config.peers.len()
+    );
 
-    let context = zmq::Context::new();
-    let publisher = context.socket(zmq::PUB).unwrap();
-    publisher
-        .bind(format!("tcp://*:{}", 5550 + id).as_str())
-        .expect("failed binding publisher");
+    if *matches.get_one::<bool>("simulate").unwrap_or(&false) {
+        let seed = *matches.get_one::<u64>("seed").unwrap_or(&0);
+        let duration = *matches.get_one::<f64>("duration").unwrap_or(&120.0);
+        let duration = Duration::from_secs_f64(duration);
+        let restart = matches
+            .get_one::<f64>("restart")
+            .map(|r| Duration::from_secs_f64(*r));
+        if let Some(sizes) = matches.get_many::<usize>("scale") {
+            let sizes: Vec<usize> = sizes.cloned().collect();
+            match config.swim {
+                true => sim::scale::<Swim>(&sizes, &config, seed, duration),
+                false => sim::scale::<Membership>(&sizes, &config, seed, duration),
+            }
+            return Ok(());
+        }
+        match config.swim {
+            true => sim::run::<Swim>(num, &config, seed, duration, restart),
+            false => sim::run::<Membership>(num, &config, seed, duration, restart),
+        }
+        return Ok(());
+    }
 
-    let mut nodes = BTreeMap::new();
+    if config.transport == Backend::Memory {
+        return match config.swim {
+            true => in_memory::<Swim>(num, &config),
+            false => in_memory::<Membership>(num, &config),
+        };
+    }
+    let id: usize = matches
+        .get_one::<String>("node")
+        .expect("missing node id")
+        .parse()
+        .expect("invalid node id");
+    match (config.swim, config.transport) {
+        (true, Backend::Udp) => node::<Swim, _>(id, num, &config, Udp::bind(&config.bind(id))),
+        (false, Backend::Udp) => {
+            node::<Membership, _>(id, num, &config, Udp::bind(&config.bind(id)))
+        }
+        (true, _) => node::<Swim, _>(id, num, &config, Zmq::bind(&config.bind(id))),
+        (false, _) => node::<Membership, _>(id, num, &config, Zmq::bind(&config.bind(id))),
+    }
+}
+
+/// Runs all `num` nodes as threads connected by in-memory channels.
+fn in_memory<D: Detector>(num: usize, config: &Config) -> io::Result<()> {
+    thread::scope(|scope| {
+        let nodes: Vec<_> = transport::network(num)
+            .into_iter()
+            .enumerate()
+            .map(|(i, transport)| scope.spawn(move || node::<D, _>(i, num, config, transport)))
+            .collect();
+        nodes
+            .into_iter()
+            .try_for_each(|node| node.join().expect("node thread panicked"))
+    })
+}
+
+fn node<D: Detector, T: Transport>(
+    id: usize,
+    num: usize,
+    config: &Config,
+    mut transport: T,
+) -> io::Result<()> {
+    let start = Instant::now();
+    // a restarted node starts with a higher generation, so its heartbeats are not stale
+    let generation = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+
+    log(
+        id,
+        format!("starting with generation {generation}").as_str(),
+    );
+
+    let mut detector = D::new(id, config.addr(id), generation, config, start.elapsed());
     for i in 0..num {
-        nodes.insert(i, Node::default());
-    }
-    let nodes = Arc::new(Mutex::new(nodes));
-
-    // subscriber thread
-    let thread_nodes = Arc::clone(&nodes);
-    thread::spawn(move || {
-        let context = zmq::Context::new();
-        let mut subscribers = HashMap::new();
-        for i in 0..num {
-            if i != id {
-                let subscriber = context.socket(zmq::SUB).unwrap();
-                subscriber
-                    .connect(format!("tcp://localhost:{}", 5550 + i).as_str())
-                    .expect("failed connecting subscriber");
-                subscriber.set_rcvtimeo(100).unwrap();
-                subscriber.set_subscribe(b"").expect("failed subscribing");
-                subscribers.insert(i, subscriber);
+        detector.add(i, config.peer(i), start.elapsed());
+    }
+
+    // get the current membership list from the seeds
+    if !config.seeds.is_empty() {
+        match join::join(id, &config.addr(id), &config.seeds, &mut transport) {
+            Some(reply) => {
+                detector.merge(reply.heartbeats, &reply.peers, start.elapsed());
             }
+            None => log(id, "No seed answered, starting with initial nodes"),
         }
-        loop {
-            for (i, subscriber) in subscribers.iter() {
-                if let Ok(Ok(envelope)) = subscriber.recv_string(0) {
-                    let message = subscriber.recv_string(0).unwrap().unwrap();
-                    let message: Message = serde_json::from_str(&message).unwrap();
+    }
 
-                    if envelope.eq("GOSSIP") & (message.receiver_id == id) {
-                        log(
-                            id,
-                            format!(
-                                "Recv msg_id {} heartbeats {:?} received from node {i}",
-                                message.msg_id, message.heartbeats
-                            )
-                            .as_str(),
-                        );
+    // local endpoint for membership queries and change events
+    let mut control = config.control.then(|| {
+        Control::bind(
+            id,
+            &config.control_bind(id),
+            &config.events_bind(id),
+            &detector,
+            start.elapsed(),
+        )
+    });
 
-                        // update timestamp and reset state
-                        thread_nodes.lock().unwrap().get_mut(i).unwrap().updated = Instant::now();
-                        thread_nodes.lock().unwrap().get_mut(i).unwrap().state = State::Running;
-
-                        // update heartbeats with received ones
-                        let mut nodes = thread_nodes.lock().unwrap();
-                        message.heartbeats.into_iter().for_each(|(j, received)| {
-                            let node = nodes.get_mut(&j).unwrap();
-                            // skip nodes which are faulty or removed
-                            if matches!(node.state, State::Running) {
-                                node.heartbeat = (node.heartbeat).max(received);
-                            }
-                        });
-                    }
-                } else {
-                    continue;
-                }
+    // SIGINT and SIGTERM stop the node like a scheduled crash
+    let terminate = Arc::new(AtomicBool::new(false));
+    for signal in [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+        signal_hook::flag::register(signal, Arc::clone(&terminate))
+            .expect("failed registering signal handler");
+    }
+    // vector clock over the gossip messages written as ShiViz log
+    let mut trace = config.shiviz.as_ref().map(|dir| Trace::open(dir, id));
+    let mut rng = rand::thread_rng();
+    // messages held back by injected delays
+    let mut pending: Vec<(Duration, usize, Stamped<D::Message>)> = Vec::new();
+    let mut next_tick = Duration::ZERO;
+    let mut next_check = T_CHECK;
+    while !terminate.load(Ordering::Relaxed) {
+        let now = start.elapsed();
+        let mut changes = Vec::new();
+        if now >= next_check {
+            let (messages, checked) = detector.check(&mut rng, now);
+            changes.extend(checked);
+            send(id, &mut transport, &detector.addrs(), messages, &mut trace);
+            next_check += T_CHECK;
+        }
+        if now >= next_tick {
+            let (messages, ticked) = detector.tick(&mut rng, now);
+            changes.extend(ticked);
+            send(id, &mut transport, &detector.addrs(), messages, &mut trace);
+            next_tick += config.t_gossip();
+
+            if config.crash.due(&mut rng, now) {
+                break;
             }
         }
-    });
 
-    // t_fail check thread
-    let thread_nodes = Arc::clone(&nodes);
-    thread::spawn(move || loop {
-        thread::sleep(Duration::from_secs(1));
-        for (i, node) in thread_nodes
-            .lock()
-            .unwrap()
-            .iter_mut()
-            .filter(|(i, _)| !(*i).eq(&id))
-        {
-            if matches!(node.state, State::Running)
-                & (node.updated.elapsed() > Duration::from_secs(T_FAIL))
-            {
-                log(id, format!("Node {i} set to faulty").as_str());
-                node.state = State::Faulty;
-                node.updated = Instant::now();
-            } else if (matches!(node.state, State::Faulty))
-                & (node.updated.elapsed() > Duration::from_secs(T_CLEANUP))
-            {
-                log(id, format!("Node {i} removed from neighbor list").as_str());
-                node.state = State::Removed;
+        let due: Vec<(Duration, usize, Stamped<D::Message>)>;
+        (due, pending) = pending.into_iter().partition(|(at, _, _)| *at <= now);
+        for (_, i, stamped) in due {
+            if let (Some(trace), Some(clock)) = (trace.as_mut(), &stamped.clock) {
+                trace.receive(i, clock);
             }
+            let (messages, received) = detector.receive(&mut rng, i, stamped.message, now);
+            changes.extend(received);
+            send(id, &mut transport, &detector.addrs(), messages, &mut trace);
         }
-    });
 
-    // publisher
-    let mut rng = rand::thread_rng();
-    let mut msg_id = 0;
-    loop {
-        let receiver_id = nodes
-            .lock()
-            .unwrap()
+        // single event loop, waits for messages and join requests until the next timer
+        // or delayed message is due
+        let next = pending
             .iter()
-            .filter(|(i, node)| !(*i).eq(&id) & !matches!(node.state, State::Removed))
-            .map(|(i, _)| i)
-            .cloned()
-            .choose(&mut rng);
-
-        if let Some(receiver_id) = receiver_id {
-            nodes.lock().unwrap().get_mut(&id).unwrap().heartbeat += 1;
-            let heartbeats: BTreeMap<usize, u64> = nodes
-                .lock()
-                .unwrap()
-                .iter()
-                .filter(|(_, node)| !matches!(node.state, State::Removed))
-                .map(|(i, node)| (*i, node.heartbeat))
-                .collect();
-            log(
-                id,
-                format!(
-                    "Send msg_id {msg_id} heartbeats {:?} to node {receiver_id}",
-                    heartbeats
-                )
-                .as_str(),
-            );
-            publisher
-                .send("GOSSIP", zmq::SNDMORE)
-                .expect("failed sending second envelope");
-            let message = Message {
-                msg_id,
-                receiver_id,
-                heartbeats,
-            };
-            publisher.send(serde_json::to_string(&message).unwrap().as_str(), 0)?;
-            msg_id += 1;
-            thread::sleep(Duration::from_secs(T_GOSSIP));
-        } else {
-            log(id, "No nodes in neighbor list");
-            thread::sleep(Duration::from_secs(T_GOSSIP));
+            .map(|(at, _, _)| *at)
+            .fold(next_tick.min(next_check), Duration::min);
+        let wait = next.saturating_sub(start.elapsed());
+        let frame = transport.recv(wait);
+        // malformed frames and frames of other versions are logged and dropped
+        match frame.as_deref().map(wire::decode) {
+            Some(Ok((Tag::Join, _, request))) => {
+                let now = start.elapsed();
+                let joined = join::answer(id, request, &mut detector, &mut transport, now);
+                changes.extend(joined.map(|joined| (joined, State::Running)));
+            }
+            // late reply to a join
+            Some(Ok((Tag::Members, _, _))) => {}
+            Some(Ok((_, i, body))) => match wire::body::<Stamped<D::Message>>(body) {
+                Ok(stamped) => {
+                    let now = start.elapsed();
+                    let time = config.faults.time(now);
+                    let deliveries = config.faults.deliveries(&mut rng, i, id, time);
+                    if deliveries.is_empty() {
+                        log(
+                            id,
+                            format!("Dropped msg_id {} from node {i}", stamped.message.msg_id())
+                                .as_str(),
+                        );
+                    }
+                    for delay in deliveries {
+                        pending.push((now + delay, i, stamped.clone()));
+                    }
+                }
+                Err(e) => log(id, format!("Dropped frame from node {i}: {e}").as_str()),
+            },
+            Some(Err(e)) => log(id, format!("Dropped frame: {e}").as_str()),
+            None => {}
         }
 
-        if rng.gen_range(0..10) < 1 {
-            break;
+        if let Some(trace) = trace.as_mut() {
+            for (j, state) in changes.iter() {
+                trace.local(format!("Node {j} {state:?}").as_str());
+            }
+        }
+        if let Some(control) = control.as_mut() {
+            let now = start.elapsed();
+            control.publish(&changes, now);
+            control.serve(&detector, now);
         }
     }
     log(id, "Terminating ...");
+    log(id, format!("Traffic {}", transport.traffic()).as_str());
+    if config.leave {
+        let messages = detector.leave();
+        send(id, &mut transport, &detector.addrs(), messages, &mut trace);
+        // give the sockets time to flush before the process exits
+        thread::sleep(LEAVE_FLUSH);
+    }
+    if let Some(trace) = trace.as_mut() {
+        trace.leave();
+    }
     Ok(())
 }
+
+/// Sends every message to the address of its receiver, stamped with the vector clock
+/// if a trace is written. Failed sends are logged and skipped, an unreachable peer is
+/// detected like a crashed one.
+fn send<M: Packet + Serialize, T: Transport>(
+    id: usize,
+    transport: &mut T,
+    addrs: &BTreeMap<usize, String>,
+    messages: Vec<M>,
+    trace: &mut Option<Trace>,
+) {
+    for message in messages {
+        let to = message.receiver_id();
+        if let Some(addr) = addrs.get(&to) {
+            let tag = message.tag();
+            let stamped = Stamped {
+                clock: trace.as_mut().map(Trace::send),
+                message,
+            };
+            if let Err(e) = transport.send(to, addr, &wire::encode(tag, id, &stamped)) {
+                log(
+                    id,
+                    format!("Failed sending to node {to} at {addr}: {e}").as_str(),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,752 @@
+use rand::{seq::IteratorRandom, Rng};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::{config::Config, cyclon::View, log, phi::Arrivals, wire::Tag};
+
+/// Interval of the timeout checks.
+pub const T_CHECK: Duration = Duration::from_millis(100);
+
+/// Generation and heartbeat or incarnation of a node, compared in this order.
+/// A restarted node has a higher generation, so its counters start over.
+pub type Version = (u64, u64);
+
+/// Addressing of a message, every message is delivered to its receiver only.
+pub trait Packet {
+    fn msg_id(&self) -> usize;
+    fn receiver_id(&self) -> usize;
+    /// Type of the message on the wire.
+    fn tag(&self) -> Tag;
+}
+
+/// Failure detection protocol run by the ZMQ nodes and the simulation. Methods take the time
+/// since start, so the same logic runs on the real and the virtual clock, and return the
+/// messages to send and the nodes whose state changed.
+pub trait Detector {
+    type Message: Packet + Clone + Serialize + DeserializeOwned;
+
+    fn new(id: usize, addr: String, generation: u64, config: &Config, now: Duration) -> Self;
+    /// Addresses of all known nodes, including removed ones.
+    fn addrs(&self) -> BTreeMap<usize, String>;
+    /// Adds a node known in advance or through a join request, removed nodes are reset.
+    fn add(&mut self, i: usize, addr: String, now: Duration);
+    /// Versions of all not removed nodes sent to joining nodes.
+    fn heartbeats(&self) -> BTreeMap<usize, Version>;
+    fn peers(&self) -> BTreeMap<usize, String>;
+    /// Merges the versions of a join reply.
+    fn merge(
+        &mut self,
+        heartbeats: BTreeMap<usize, Version>,
+        peers: &BTreeMap<usize, String>,
+        now: Duration,
+    ) -> Vec<(usize, State)>;
+    /// Called every T_GOSSIP.
+    fn tick<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        now: Duration,
+    ) -> (Vec<Self::Message>, Vec<(usize, State)>);
+    /// Handles a message from node `from`.
+    fn receive<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        from: usize,
+        message: Self::Message,
+        now: Duration,
+    ) -> (Vec<Self::Message>, Vec<(usize, State)>);
+    /// Called every T_CHECK.
+    fn check<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        now: Duration,
+    ) -> (Vec<Self::Message>, Vec<(usize, State)>);
+    /// Announces that this node leaves, sent right before terminating.
+    fn leave(&mut self) -> Vec<Self::Message>;
+    /// Membership table including the own node, answered to control queries.
+    fn table(&self, now: Duration) -> BTreeMap<usize, Status>;
+    /// Generation node `i` is known with, 0 before its first heartbeat.
+    fn generation(&self, i: usize) -> Option<u64>;
+}
+
+/// Entry of the membership table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Status {
+    pub addr: String,
+    pub state: State,
+    pub generation: u64,
+    /// Heartbeat counter, the incarnation in SWIM.
+    pub heartbeat: u64,
+    /// Seconds since the last update, always 0 for the own node.
+    pub age: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Kind {
+    Gossip,
+    /// The sender leaves, the heartbeats only contain its final heartbeat.
+    Leave,
+    /// Push-pull reply with the entries the gossiping node was missing or had stale.
+    Pull,
+    /// Cyclon shuffle with a sample of the view, answered with a sample of the receiver's view.
+    Shuffle,
+    ShuffleReply,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub msg_id: usize,
+    pub receiver_id: usize,
+    pub kind: Kind,
+    pub heartbeats: BTreeMap<usize, Version>,
+    pub peers: BTreeMap<usize, String>,
+    /// View entries with their age in shuffles.
+    pub sample: Vec<(usize, u32)>,
+    /// Final versions of nodes that left within T_CLEANUP, so the leave also reaches nodes
+    /// that missed it.
+    pub left: BTreeMap<usize, Version>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum State {
+    Running,
+    /// Only used by SWIM, between running and faulty.
+    Suspect,
+    Faulty,
+    Removed,
+    /// Announced a graceful leave, only a higher generation brings the node back.
+    Left,
+}
+
+/// Local view of a node, `updated` is the time since start of the local node.
+/// The generation is 0 until the first heartbeat of the node is received.
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub state: State,
+    pub updated: Duration,
+    pub generation: u64,
+    pub heartbeat: u64,
+    pub addr: String,
+    pub arrivals: Arrivals,
+}
+
+impl Node {
+    fn new(addr: String, now: Duration, t_gossip: Duration) -> Self {
+        Self {
+            state: State::Running,
+            updated: now,
+            generation: 0,
+            heartbeat: 0,
+            addr,
+            arrivals: Arrivals::new(now, t_gossip),
+        }
+    }
+}
+
+impl Packet for Message {
+    fn msg_id(&self) -> usize {
+        self.msg_id
+    }
+
+    fn receiver_id(&self) -> usize {
+        self.receiver_id
+    }
+
+    fn tag(&self) -> Tag {
+        match self.kind {
+            Kind::Leave => Tag::Leave,
+            Kind::Gossip | Kind::Pull | Kind::Shuffle | Kind::ShuffleReply => Tag::Gossip,
+        }
+    }
+}
+
+/// Membership table of a single node for heartbeat gossip.
+pub struct Membership {
+    pub id: usize,
+    pub nodes: BTreeMap<usize, Node>,
+    msg_id: usize,
+    t_gossip: Duration,
+    t_fail: Duration,
+    t_cleanup: Duration,
+    /// Phi thresholds for faulty and removal, replace T_FAIL and T_CLEANUP when set.
+    phi: Option<(f64, f64)>,
+    /// Receivers per gossip round.
+    fanout: usize,
+    /// Answer gossip with the entries the sender is missing.
+    push_pull: bool,
+    /// Partial view heartbeats are gossiped and monitored in, all known nodes when not set.
+    view: Option<View>,
+    /// Peers of unanswered shuffles, monitored until they answer.
+    shuffles: BTreeMap<usize, Duration>,
+}
+
+impl Membership {
+    /// Picks `fanout` random receivers and returns the next gossip message for each of them.
+    pub fn gossip<R: Rng>(&mut self, rng: &mut R, now: Duration) -> Vec<Message> {
+        let receivers = self
+            .nodes
+            .iter()
+            .filter(|(i, node)| {
+                !(*i).eq(&self.id)
+                    & !matches!(node.state, State::Removed | State::Left)
+                    & self.in_view(**i)
+            })
+            .map(|(i, _)| i)
+            .cloned()
+            .choose_multiple(rng, self.fanout);
+
+        if receivers.is_empty() {
+            log(self.id, "No nodes in neighbor list");
+            return Vec::new();
+        }
+        self.nodes.get_mut(&self.id).unwrap().heartbeat += 1;
+        let (heartbeats, peers) = self.entries();
+        let left = self.departed(now);
+        receivers
+            .into_iter()
+            .map(|receiver_id| {
+                let mut message = self.message(
+                    receiver_id,
+                    Kind::Gossip,
+                    heartbeats.clone(),
+                    peers.clone(),
+                    Vec::new(),
+                );
+                message.left = left.clone();
+                message
+            })
+            .collect()
+    }
+
+    fn message(
+        &mut self,
+        receiver_id: usize,
+        kind: Kind,
+        heartbeats: BTreeMap<usize, Version>,
+        peers: BTreeMap<usize, String>,
+        sample: Vec<(usize, u32)>,
+    ) -> Message {
+        let (what, entries) = match kind {
+            Kind::Pull => ("pull heartbeats", format!("{heartbeats:?}")),
+            Kind::Shuffle => ("shuffle", format!("{sample:?}")),
+            Kind::ShuffleReply => ("shuffle reply", format!("{sample:?}")),
+            _ => ("heartbeats", format!("{heartbeats:?}")),
+        };
+        log(
+            self.id,
+            format!(
+                "Send msg_id {} {what} {entries} to node {receiver_id}",
+                self.msg_id
+            )
+            .as_str(),
+        );
+        let message = Message {
+            msg_id: self.msg_id,
+            receiver_id,
+            kind,
+            heartbeats,
+            peers,
+            sample,
+            left: BTreeMap::new(),
+        };
+        self.msg_id += 1;
+        message
+    }
+
+    fn in_view(&self, i: usize) -> bool {
+        self.view.as_ref().is_none_or(|view| view.contains(i))
+    }
+
+    /// Heartbeats and addresses gossiped, only of the own node and the view if one is kept.
+    fn entries(&self) -> (BTreeMap<usize, Version>, BTreeMap<usize, String>) {
+        let gossiped = |i: &usize| (*i == self.id) | self.in_view(*i);
+        let heartbeats = self
+            .heartbeats()
+            .into_iter()
+            .filter(|(i, _)| gossiped(i))
+            .collect();
+        let peers = self
+            .peers()
+            .into_iter()
+            .filter(|(i, _)| gossiped(i))
+            .collect();
+        (heartbeats, peers)
+    }
+
+    /// Final versions of the nodes that left within T_CLEANUP, gossiped regardless of the view.
+    fn departed(&self, now: Duration) -> BTreeMap<usize, Version> {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| {
+                matches!(node.state, State::Left)
+                    & (now.saturating_sub(node.updated) <= self.t_cleanup)
+            })
+            .map(|(i, node)| (*i, (node.generation, node.heartbeat)))
+            .collect()
+    }
+
+    /// Marks known nodes as left, keeping the final version so older gossip about them
+    /// is stale. Leaves of an earlier generation than the known one are ignored.
+    fn depart(&mut self, left: BTreeMap<usize, Version>, now: Duration) -> Vec<(usize, State)> {
+        let mut changes = Vec::new();
+        for (j, version) in left {
+            let Some(node) = self.nodes.get_mut(&j) else {
+                continue;
+            };
+            if (j == self.id) | matches!(node.state, State::Left) | (version.0 < node.generation) {
+                continue;
+            }
+            (node.generation, node.heartbeat) = version.max((node.generation, node.heartbeat));
+            log(
+                self.id,
+                format!("Node {j} left in generation {}", node.generation).as_str(),
+            );
+            node.state = State::Left;
+            node.updated = now;
+            changes.push((j, State::Left));
+            self.forget(j);
+        }
+        changes
+    }
+
+    /// Starts a shuffle with the oldest peer of the view, an empty view is first refilled
+    /// with random running nodes of the table.
+    fn shuffle<R: Rng>(&mut self, rng: &mut R, now: Duration) -> Option<Message> {
+        let candidates: Vec<usize> = self
+            .nodes
+            .iter()
+            .filter(|(i, node)| !(*i).eq(&self.id) & matches!(node.state, State::Running))
+            .map(|(i, _)| *i)
+            .collect();
+        let view = self.view.as_mut()?;
+        if view.is_empty() {
+            view.fill(candidates.into_iter(), rng);
+            self.prune();
+        }
+        let (peer, sample) = self.view.as_mut()?.start_shuffle(self.id, rng)?;
+        self.shuffles.insert(peer, now);
+        let peers = self.addrs_of(&sample);
+        Some(self.message(peer, Kind::Shuffle, BTreeMap::new(), peers, sample))
+    }
+
+    fn addrs_of(&self, sample: &[(usize, u32)]) -> BTreeMap<usize, String> {
+        sample
+            .iter()
+            .filter_map(|(j, _)| self.nodes.get(j).map(|node| (*j, node.addr.clone())))
+            .collect()
+    }
+
+    /// Received view entries that may enter the view, nodes known to be faulty, removed or left
+    /// and nodes without address are skipped.
+    fn admissible(&self, message: &Message) -> Vec<(usize, u32)> {
+        message
+            .sample
+            .iter()
+            .filter(|(j, _)| match self.nodes.get(j) {
+                Some(node) => matches!(node.state, State::Running),
+                None => message.peers.contains_key(j),
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Starts monitoring the nodes that entered the view, as if their heartbeat was just received.
+    fn enter(&mut self, added: Vec<usize>, peers: &BTreeMap<usize, String>, now: Duration) {
+        for j in added {
+            log(self.id, format!("Node {j} entered view").as_str());
+            match self.nodes.get_mut(&j) {
+                Some(node) => {
+                    node.updated = now;
+                    node.arrivals.reset(now);
+                }
+                None => match peers.get(&j) {
+                    Some(addr) => {
+                        let node = Node::new(addr.clone(), now, self.t_gossip);
+                        self.nodes.insert(j, node);
+                    }
+                    None => log(
+                        self.id,
+                        format!("Dropped node {j} without address").as_str(),
+                    ),
+                },
+            }
+        }
+        self.prune();
+    }
+
+    /// Forgets running nodes that are neither in the view nor asked for a shuffle,
+    /// they are no longer monitored. An empty view is refilled from them first.
+    fn prune(&mut self) {
+        if self.view.as_ref().is_none_or(View::is_empty) {
+            return;
+        }
+        let forgotten: Vec<usize> = self
+            .nodes
+            .iter()
+            .filter(|(i, node)| {
+                !(*i).eq(&self.id)
+                    & matches!(node.state, State::Running)
+                    & !self.in_view(**i)
+                    & !self.shuffles.contains_key(*i)
+            })
+            .map(|(i, _)| *i)
+            .collect();
+        for i in forgotten {
+            self.nodes.remove(&i);
+        }
+    }
+
+    /// Stops monitoring a faulty or departed node.
+    fn forget(&mut self, i: usize) {
+        if let Some(view) = self.view.as_mut() {
+            view.remove(i);
+        }
+        self.shuffles.remove(&i);
+    }
+
+    /// Merges received heartbeats into the table, a node is fresh whenever its heartbeat
+    /// increases, no matter from which node the heartbeat was received. Unknown nodes are
+    /// only added if `learn` is set.
+    fn update(
+        &mut self,
+        heartbeats: BTreeMap<usize, Version>,
+        peers: &BTreeMap<usize, String>,
+        now: Duration,
+        learn: bool,
+    ) -> Vec<(usize, State)> {
+        let mut changes = Vec::new();
+        for (j, (generation, received)) in heartbeats {
+            if (j == self.id) | (!learn & !self.nodes.contains_key(&j)) {
+                continue;
+            }
+            if !self.nodes.contains_key(&j) {
+                // a frame can carry a heartbeat without the address of its node
+                let Some(addr) = peers.get(&j) else {
+                    log(
+                        self.id,
+                        format!("Dropped node {j} without address").as_str(),
+                    );
+                    continue;
+                };
+                log(
+                    self.id,
+                    format!("Node {j} learned through gossip in generation {generation}").as_str(),
+                );
+                changes.push((j, State::Running));
+                let node = Node::new(addr.clone(), now, self.t_gossip);
+                self.nodes.insert(j, node);
+            }
+            let node = self.nodes.get_mut(&j).unwrap();
+            // stale heartbeats of faulty or removed nodes are ignored, a higher one
+            // or any heartbeat of a higher generation shows the node is alive again,
+            // a node that left only comes back with a higher generation
+            let left = matches!(node.state, State::Left) & (generation <= node.generation);
+            if !left & ((generation, received) > (node.generation, node.heartbeat)) {
+                if (generation > node.generation) & (node.generation > 0) {
+                    log(
+                        self.id,
+                        format!("Node {j} restarted with generation {generation}").as_str(),
+                    );
+                }
+                match node.state {
+                    State::Faulty => log(
+                        self.id,
+                        format!("Node {j} recovered in generation {generation}").as_str(),
+                    ),
+                    State::Removed | State::Left => log(
+                        self.id,
+                        format!("Node {j} rejoined in generation {generation}").as_str(),
+                    ),
+                    State::Running | State::Suspect => {}
+                }
+                if !matches!(node.state, State::Running) {
+                    changes.push((j, State::Running));
+                }
+                match node.state {
+                    State::Running => node.arrivals.record(now),
+                    _ => node.arrivals.reset(now),
+                }
+                node.generation = generation;
+                node.heartbeat = received;
+                node.updated = now;
+                node.state = State::Running;
+            }
+        }
+        changes
+    }
+}
+
+impl Detector for Membership {
+    type Message = Message;
+
+    fn new(id: usize, addr: String, generation: u64, config: &Config, now: Duration) -> Self {
+        let mut node = Node::new(addr, now, config.t_gossip());
+        node.generation = generation;
+        Self {
+            id,
+            nodes: BTreeMap::from([(id, node)]),
+            msg_id: 0,
+            t_gossip: config.t_gossip(),
+            t_fail: config.t_fail(),
+            t_cleanup: config.t_cleanup(),
+            phi: config.phi.then_some((config.phi_fail, config.phi_cleanup)),
+            fanout: config.fanout,
+            push_pull: config.push_pull,
+            view: (config.view > 0).then(|| View::new(config.view, config.shuffle)),
+            shuffles: BTreeMap::new(),
+        }
+    }
+
+    fn addrs(&self) -> BTreeMap<usize, String> {
+        self.nodes
+            .iter()
+            .map(|(i, node)| (*i, node.addr.clone()))
+            .collect()
+    }
+
+    fn add(&mut self, i: usize, addr: String, now: Duration) {
+        let node = self
+            .nodes
+            .entry(i)
+            .or_insert_with(|| Node::new(addr.clone(), now, self.t_gossip));
+        if matches!(node.state, State::Removed | State::Left) {
+            *node = Node::new(addr.clone(), now, self.t_gossip);
+        }
+        node.addr = addr;
+    }
+
+    fn heartbeats(&self) -> BTreeMap<usize, Version> {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| !matches!(node.state, State::Removed | State::Left))
+            .map(|(i, node)| (*i, (node.generation, node.heartbeat)))
+            .collect()
+    }
+
+    fn peers(&self) -> BTreeMap<usize, String> {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| !matches!(node.state, State::Removed | State::Left))
+            .map(|(i, node)| (*i, node.addr.clone()))
+            .collect()
+    }
+
+    /// Join replies add every node, a partial view only learns nodes through shuffles.
+    fn merge(
+        &mut self,
+        heartbeats: BTreeMap<usize, Version>,
+        peers: &BTreeMap<usize, String>,
+        now: Duration,
+    ) -> Vec<(usize, State)> {
+        self.update(heartbeats, peers, now, true)
+    }
+
+    fn tick<R: Rng>(&mut self, rng: &mut R, now: Duration) -> (Vec<Message>, Vec<(usize, State)>) {
+        if self.phi.is_some() {
+            let phi: BTreeMap<usize, String> = self
+                .nodes
+                .iter()
+                .filter(|(i, node)| {
+                    !(*i).eq(&self.id) & !matches!(node.state, State::Removed | State::Left)
+                })
+                .map(|(i, node)| (*i, format!("{:.2}", node.arrivals.phi(now))))
+                .collect();
+            log(self.id, format!("Phi {phi:?}").as_str());
+        }
+        let mut messages: Vec<Message> = self.shuffle(rng, now).into_iter().collect();
+        messages.extend(self.gossip(rng, now));
+        (messages, Vec::new())
+    }
+
+    fn receive<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        from: usize,
+        message: Message,
+        now: Duration,
+    ) -> (Vec<Message>, Vec<(usize, State)>) {
+        let (what, entries) = match message.kind {
+            Kind::Shuffle | Kind::ShuffleReply => ("shuffle", format!("{:?}", message.sample)),
+            _ => ("heartbeats", format!("{:?}", message.heartbeats)),
+        };
+        log(
+            self.id,
+            format!(
+                "Recv msg_id {} {what} {entries} received from node {from}",
+                message.msg_id
+            )
+            .as_str(),
+        );
+        // a partial view only learns nodes through shuffles
+        let learn = self.view.is_none();
+        match message.kind {
+            Kind::Gossip if self.push_pull => {
+                let received = message.heartbeats.clone();
+                let mut changes = self.update(message.heartbeats, &message.peers, now, learn);
+                changes.extend(self.depart(message.left, now));
+                // after the merge every entry is at least as new as the received one
+                let (heartbeats, peers) = self.entries();
+                let newer: BTreeMap<usize, Version> = heartbeats
+                    .into_iter()
+                    .filter(|(j, version)| received.get(j).is_none_or(|r| r < version))
+                    .collect();
+                if newer.is_empty() {
+                    return (Vec::new(), changes);
+                }
+                let peers = peers
+                    .into_iter()
+                    .filter(|(j, _)| newer.contains_key(j))
+                    .collect();
+                let mut reply = self.message(from, Kind::Pull, newer, peers, Vec::new());
+                reply.left = self.departed(now);
+                (vec![reply], changes)
+            }
+            Kind::Gossip | Kind::Pull => {
+                let mut changes = self.update(message.heartbeats, &message.peers, now, learn);
+                changes.extend(self.depart(message.left, now));
+                (Vec::new(), changes)
+            }
+            Kind::Shuffle => {
+                let received = self.admissible(&message);
+                let Some(view) = self.view.as_mut() else {
+                    return (Vec::new(), Vec::new());
+                };
+                let (sample, added) = view.answer(self.id, from, &received, rng);
+                self.enter(added, &message.peers, now);
+                let peers = self.addrs_of(&sample);
+                let reply = self.message(from, Kind::ShuffleReply, BTreeMap::new(), peers, sample);
+                (vec![reply], Vec::new())
+            }
+            Kind::ShuffleReply => {
+                self.shuffles.remove(&from);
+                let received = self.admissible(&message);
+                if let Some(view) = self.view.as_mut() {
+                    let added = view.reply(self.id, &received);
+                    self.enter(added, &message.peers, now);
+                }
+                (Vec::new(), Vec::new())
+            }
+            Kind::Leave => {
+                let Some(version) = message.heartbeats.get(&from).copied() else {
+                    log(
+                        self.id,
+                        format!("Dropped leave of node {from} without its heartbeat").as_str(),
+                    );
+                    return (Vec::new(), Vec::new());
+                };
+                (
+                    Vec::new(),
+                    self.depart(BTreeMap::from([(from, version)]), now),
+                )
+            }
+        }
+    }
+
+    /// Sets nodes faulty after T_FAIL and removes them after T_CLEANUP,
+    /// or when phi exceeds the thresholds.
+    fn check<R: Rng>(
+        &mut self,
+        _rng: &mut R,
+        now: Duration,
+    ) -> (Vec<Message>, Vec<(usize, State)>) {
+        let mut changes = Vec::new();
+        // with a partial view only the view and the peers of unanswered shuffles are monitored
+        let monitored = |i: usize| {
+            self.view.as_ref().is_none_or(|view| view.contains(i)) | self.shuffles.contains_key(&i)
+        };
+        for (i, node) in self.nodes.iter_mut().filter(|(i, _)| !(*i).eq(&self.id)) {
+            let elapsed = now.saturating_sub(node.updated);
+            let (fail, cleanup) = match self.phi {
+                Some((phi_fail, phi_cleanup)) => {
+                    let phi = node.arrivals.phi(now);
+                    (phi > phi_fail, phi > phi_cleanup)
+                }
+                None => (elapsed > self.t_fail, elapsed > self.t_cleanup),
+            };
+            if matches!(node.state, State::Running) & fail & monitored(*i) {
+                log(
+                    self.id,
+                    format!("Node {i} set to faulty in generation {}", node.generation).as_str(),
+                );
+                node.state = State::Faulty;
+                node.updated = now;
+                changes.push((*i, State::Faulty));
+            } else if (matches!(node.state, State::Faulty)) & cleanup {
+                log(
+                    self.id,
+                    format!(
+                        "Node {i} removed from neighbor list in generation {}",
+                        node.generation
+                    )
+                    .as_str(),
+                );
+                node.state = State::Removed;
+                changes.push((*i, State::Removed));
+            }
+        }
+        if self.view.is_some() {
+            for (i, _) in changes.iter().filter(|(_, state)| *state == State::Faulty) {
+                self.forget(*i);
+            }
+            // a shuffle peer still heard of after T_FAIL only lost the reply
+            let t_fail = self.t_fail;
+            self.shuffles
+                .retain(|_, sent| now.saturating_sub(*sent) <= t_fail);
+            self.prune();
+        }
+        (Vec::new(), changes)
+    }
+
+    /// Announces the leave to every known node.
+    fn leave(&mut self) -> Vec<Message> {
+        let own = &self.nodes[&self.id];
+        let heartbeats = BTreeMap::from([(self.id, (own.generation, own.heartbeat))]);
+        let receivers: Vec<usize> = self
+            .peers()
+            .into_keys()
+            .filter(|i| !i.eq(&self.id))
+            .collect();
+        receivers
+            .into_iter()
+            .map(|receiver_id| {
+                log(
+                    self.id,
+                    format!("Send leave to node {receiver_id}").as_str(),
+                );
+                let message = Message {
+                    msg_id: self.msg_id,
+                    receiver_id,
+                    kind: Kind::Leave,
+                    heartbeats: heartbeats.clone(),
+                    peers: BTreeMap::new(),
+                    sample: Vec::new(),
+                    left: BTreeMap::new(),
+                };
+                self.msg_id += 1;
+                message
+            })
+            .collect()
+    }
+
+    fn table(&self, now: Duration) -> BTreeMap<usize, Status> {
+        self.nodes
+            .iter()
+            .map(|(i, node)| {
+                let status = Status {
+                    addr: node.addr.clone(),
+                    state: node.state.clone(),
+                    generation: node.generation,
+                    heartbeat: node.heartbeat,
+                    age: match *i == self.id {
+                        true => 0.0,
+                        false => now.saturating_sub(node.updated).as_secs_f64(),
+                    },
+                };
+                (*i, status)
+            })
+            .collect()
+    }
+
+    fn generation(&self, i: usize) -> Option<u64> {
+        self.nodes.get(&i).map(|node| node.generation)
+    }
+}
@@ -0,0 +1,63 @@
+use std::{collections::VecDeque, time::Duration};
+
+/// Number of inter-arrival times kept per node.
+const WINDOW: usize = 100;
+/// Lower bound of the standard deviation relative to the mean, so regular heartbeats
+/// do not make phi explode on the slightest delay.
+const MIN_STD_RATIO: f64 = 0.5;
+
+/// Heartbeat inter-arrival times of a node for the phi accrual failure detector
+/// by Hayashibara et al.
+#[derive(Debug, Clone)]
+pub struct Arrivals {
+    last: Duration,
+    intervals: VecDeque<f64>,
+}
+
+impl Arrivals {
+    /// Starts with a single expected interval, usually T_GOSSIP.
+    pub fn new(now: Duration, expected: Duration) -> Self {
+        Self {
+            last: now,
+            intervals: VecDeque::from([expected.as_secs_f64()]),
+        }
+    }
+
+    pub fn record(&mut self, now: Duration) {
+        if self.intervals.len() == WINDOW {
+            self.intervals.pop_front();
+        }
+        self.intervals
+            .push_back(now.saturating_sub(self.last).as_secs_f64());
+        self.last = now;
+    }
+
+    /// Restarts after an outage without recording the gap.
+    pub fn reset(&mut self, now: Duration) {
+        self.last = now;
+    }
+
+    /// Suspicion level, phi = 1 means a 10% chance of a mistake, phi = 2 1% and so on.
+    pub fn phi(&self, now: Duration) -> f64 {
+        let n = self.intervals.len() as f64;
+        let mean = self.intervals.iter().sum::<f64>() / n;
+        let variance = self
+            .intervals
+            .iter()
+            .map(|i| (i - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        let std = variance.sqrt().max(mean * MIN_STD_RATIO);
+
+        // logistic approximation of the normal cdf
+        let elapsed = now.saturating_sub(self.last).as_secs_f64();
+        let y = (elapsed - mean) / std;
+        let e = (-y * (1.5976 + 0.070566 * y * y)).exp();
+        let later = if elapsed > mean {
+            e / (1.0 + e)
+        } else {
+            1.0 - 1.0 / (1.0 + e)
+        };
+        (-later.max(f64::MIN_POSITIVE).log10()).max(0.0)
+    }
+}
@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, read_to_string, File},
+    io::Write,
+};
+
+/// Captions of the trace events, shared with the merge binary.
+pub const SEND_EVENT: &str = "Send event";
+pub const RECV_EVENT: &str = "Receive event";
+const JOIN_EVENT: &str = "Join event";
+const LEAVE_EVENT: &str = "Leave event";
+
+/// Clock entries by host name.
+pub type VectorClock = BTreeMap<String, usize>;
+
+/// Payload of a gossip message, the vector clock of the sender is only attached
+/// when it writes a trace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Stamped<M> {
+    pub clock: Option<VectorClock>,
+    pub message: M,
+}
+
+/// Vector clock of a node over its gossip messages, every event is appended to a ShiViz log
+/// as caption line and `host {clock}` line. Like in the logs the project1 analyser reads, only
+/// the own entry and on receives the entry of the sender are written.
+pub struct Trace {
+    host: String,
+    clock: VectorClock,
+    file: File,
+}
+
+impl Trace {
+    /// Appends to the log of node `id` in `dir`, a restarted node continues its own clock.
+    pub fn open(dir: &str, id: usize) -> Trace {
+        fs::create_dir_all(dir).expect("failed creating trace directory");
+        let path = format!("{dir}/node{id}.log");
+        let events = read_to_string(&path).map_or(0, |data| data.lines().count() / 2);
+        let file = File::options()
+            .create(true)
+            .append(true)
+            .open(path)
+            .expect("failed opening trace");
+        let host = format!("node{id}");
+        let mut trace = Trace {
+            clock: VectorClock::from([(host.clone(), events)]),
+            host,
+            file,
+        };
+        trace.event(JOIN_EVENT, None);
+        trace
+    }
+
+    /// `sender` is the host and clock of the matching send on receives.
+    fn event(&mut self, caption: &str, sender: Option<(String, usize)>) {
+        *self.clock.get_mut(&self.host).unwrap() += 1;
+        let mut entries = VectorClock::from([(self.host.clone(), self.clock[&self.host])]);
+        entries.extend(sender);
+        let entries = serde_json::to_string(&entries).unwrap();
+        // one write per event, so a killed node leaves no half event behind
+        write!(self.file, "{caption}\n{} {entries}\n", self.host).expect("failed writing trace");
+    }
+
+    /// Local event like a state change of another node.
+    pub fn local(&mut self, caption: &str) {
+        self.event(caption, None);
+    }
+
+    /// Returns the clock to attach to the message.
+    pub fn send(&mut self) -> VectorClock {
+        self.event(SEND_EVENT, None);
+        self.clock.clone()
+    }
+
+    pub fn receive(&mut self, from: usize, clock: &VectorClock) {
+        for (host, c) in clock.iter().filter(|(host, _)| !host.eq(&&self.host)) {
+            let entry = self.clock.entry(host.clone()).or_default();
+            *entry = (*entry).max(*c);
+        }
+        let sender = format!("node{from}");
+        let sent = clock.get(&sender).cloned().unwrap_or_default();
+        self.event(RECV_EVENT, Some((sender, sent)));
+    }
+
+    pub fn leave(&mut self) {
+        self.event(LEAVE_EVENT, None);
+    }
+}
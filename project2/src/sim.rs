@@ -0,0 +1,516 @@
+use rand::{rngs::StdRng, seq::IteratorRandom, Rng, SeedableRng};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::atomic::Ordering,
+    time::Duration,
+};
+
+use crate::{
+    config::Config,
+    faults::Faults,
+    log,
+    membership::{Detector, Packet, State, Version, T_CHECK},
+    summary::summary,
+    wire, QUIET,
+};
+
+const MIN_LATENCY: Duration = Duration::from_millis(1);
+const MAX_LATENCY: Duration = Duration::from_millis(10);
+
+enum SimEvent<M> {
+    Gossip(usize),
+    Check(usize),
+    Deliver(usize, usize, M),
+    Restart(usize),
+    /// Measures how up to date the membership tables are.
+    Sample,
+}
+
+/// In-memory network, events are ordered by virtual time and the sequence number keeps
+/// insertion order on ties.
+struct Network<M> {
+    queue: BTreeMap<(Duration, usize), SimEvent<M>>,
+    seq: usize,
+    sent: usize,
+    /// Size of the sent messages in the wire format.
+    bytes: usize,
+}
+
+impl<M: Packet + Clone + Serialize> Network<M> {
+    fn schedule(&mut self, at: Duration, event: SimEvent<M>) {
+        self.queue.insert((at, self.seq), event);
+        self.seq += 1;
+    }
+
+    fn send<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        faults: &Faults,
+        from: usize,
+        messages: Vec<M>,
+        now: Duration,
+    ) {
+        for message in messages {
+            self.sent += 1;
+            self.bytes += wire::encode(message.tag(), from, &message).len();
+            let to = message.receiver_id();
+            let deliveries = faults.deliveries(rng, from, to, now);
+            if deliveries.is_empty() {
+                log(
+                    to,
+                    format!("Dropped msg_id {} from node {from}", message.msg_id()).as_str(),
+                );
+            }
+            for delay in deliveries {
+                let latency = rng.gen_range(MIN_LATENCY..=MAX_LATENCY) + delay;
+                self.schedule(now + latency, SimEvent::Deliver(to, from, message.clone()));
+            }
+        }
+    }
+}
+
+/// Node and generation, every generation of a node runs from its start or restart
+/// until it crashes or leaves.
+type Episode = (usize, u64);
+
+/// Crash, leave, detection and recovery times of every episode, observations are keyed
+/// by observer and episode and only the first one of every observer counts.
+#[derive(Debug, Default, PartialEq)]
+struct Metrics {
+    crashed: BTreeMap<Episode, Duration>,
+    left: BTreeMap<Episode, Duration>,
+    departed: BTreeMap<(usize, Episode), Duration>,
+    restarted: BTreeMap<Episode, Duration>,
+    detected: BTreeMap<(usize, Episode), Duration>,
+    removed: BTreeMap<(usize, Episode), Duration>,
+    recovered: BTreeMap<(usize, Episode), Duration>,
+    false_positives: usize,
+    /// Left nodes set to faulty by nodes the leave did not reach.
+    missed_leaves: usize,
+    /// Versions every running node is behind the own version of every other running node,
+    /// missing entries are not counted.
+    lags: Vec<u64>,
+    missing: usize,
+}
+
+/// Runs `num` nodes in one process over an in-memory network with a virtual clock,
+/// crashed nodes come back with a higher generation after `restart` if given.
+/// Every random decision is drawn from a single rng seeded with `seed`, so runs are reproducible.
+pub fn run<D: Detector>(
+    num: usize,
+    config: &Config,
+    seed: u64,
+    duration: Duration,
+    restart: Option<Duration>,
+) {
+    let (metrics, sent, bytes) = simulate::<D>(num, config, seed, duration, restart, true);
+    report(num, &metrics, sent, bytes, config.t_gossip());
+}
+
+/// Simulates every cluster size without node logs and prints the detection accuracy
+/// and message overhead of each. The tables are not sampled, which takes quadratic time.
+pub fn scale<D: Detector>(sizes: &[usize], config: &Config, seed: u64, duration: Duration) {
+    QUIET.store(true, Ordering::Relaxed);
+    // only crashes are counted, so terminated nodes fail silently instead of leaving
+    let config = Config {
+        leave: false,
+        ..config.clone()
+    };
+    for num in sizes {
+        let (metrics, sent, bytes) = simulate::<D>(*num, &config, seed, duration, None, false);
+        let crashed = metrics.crashed.len();
+        let detected = metrics
+            .crashed
+            .keys()
+            .filter(|j| metrics.detected.keys().any(|(_, i)| i == *j))
+            .count();
+        let latencies: Vec<f64> = metrics
+            .detected
+            .values()
+            .map(Duration::as_secs_f64)
+            .collect();
+        let observers = match latencies.is_empty() {
+            true => String::new(),
+            false => format!(
+                " by {:.1} nodes on average after {:.3}s",
+                latencies.len() as f64 / detected as f64,
+                latencies.iter().sum::<f64>() / latencies.len() as f64
+            ),
+        };
+        let rounds = duration.as_secs_f64() / config.t_gossip;
+        println!(
+            "{num} nodes: {detected} of {crashed} crashes detected{observers}, \
+             {:.3} false positives per node and minute, {:.2} messages per node and round of {} bytes",
+            metrics.false_positives as f64 / *num as f64 / duration.as_secs_f64() * 60.0,
+            sent as f64 / *num as f64 / rounds,
+            bytes / sent.max(1)
+        );
+    }
+}
+
+/// Returns the metrics, the number of messages sent and their size in bytes.
+fn simulate<D: Detector>(
+    num: usize,
+    config: &Config,
+    seed: u64,
+    duration: Duration,
+    restart: Option<Duration>,
+    sampled: bool,
+) -> (Metrics, usize, usize) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    // with a partial view a node starts out knowing a random view, like after joining
+    let partial = (config.view > 0) & !config.swim;
+    let start = |i: usize, generation: u64, now: Duration, rng: &mut StdRng| {
+        let mut detector = D::new(i, config.peer(i), generation, config, now);
+        let known = match partial {
+            true => (0..num)
+                .filter(|j| *j != i)
+                .choose_multiple(rng, config.view),
+            false => (0..num).collect(),
+        };
+        for j in known {
+            detector.add(j, config.peer(j), now);
+        }
+        detector
+    };
+    let mut generations = vec![1; num];
+    let mut nodes: Vec<D> = (0..num)
+        .map(|i| start(i, 1, Duration::ZERO, &mut rng))
+        .collect();
+
+    let mut network = Network {
+        queue: BTreeMap::new(),
+        seq: 0,
+        sent: 0,
+        bytes: 0,
+    };
+    if sampled {
+        network.schedule(config.t_gossip(), SimEvent::Sample);
+    }
+    for i in 0..num {
+        let offset = config.t_gossip().mul_f64(rng.gen());
+        network.schedule(offset, SimEvent::Gossip(i));
+        network.schedule(T_CHECK, SimEvent::Check(i));
+    }
+
+    let mut down: BTreeSet<usize> = BTreeSet::new();
+    let mut metrics = Metrics::default();
+    while let Some(((now, _), event)) = network.queue.pop_first() {
+        if now > duration {
+            break;
+        }
+        let (observer, (messages, changes)) = match event {
+            SimEvent::Gossip(i) if !down.contains(&i) => {
+                let (mut messages, changes) = nodes[i].tick(&mut rng, now);
+                // same crash schedule as the real nodes
+                if config.crash.due(&mut rng, now) {
+                    log(
+                        i,
+                        format!("Terminating at {:.3}s", now.as_secs_f64()).as_str(),
+                    );
+                    down.insert(i);
+                    if config.leave {
+                        messages.extend(nodes[i].leave());
+                        metrics.left.insert((i, generations[i]), now);
+                    } else {
+                        metrics.crashed.insert((i, generations[i]), now);
+                    }
+                    if let Some(restart) = restart {
+                        network.schedule(now + restart, SimEvent::Restart(i));
+                    }
+                } else {
+                    network.schedule(now + config.t_gossip(), SimEvent::Gossip(i));
+                }
+                (i, (messages, changes))
+            }
+            SimEvent::Check(i) if !down.contains(&i) => {
+                network.schedule(now + T_CHECK, SimEvent::Check(i));
+                (i, nodes[i].check(&mut rng, now))
+            }
+            SimEvent::Deliver(to, from, message) if !down.contains(&to) => {
+                (to, nodes[to].receive(&mut rng, from, message, now))
+            }
+            SimEvent::Restart(i) => {
+                generations[i] += 1;
+                log(
+                    i,
+                    format!(
+                        "Restarting at {:.3}s with generation {}",
+                        now.as_secs_f64(),
+                        generations[i]
+                    )
+                    .as_str(),
+                );
+                nodes[i] = start(i, generations[i], now, &mut rng);
+                down.remove(&i);
+                metrics.restarted.insert((i, generations[i]), now);
+                network.schedule(now, SimEvent::Gossip(i));
+                network.schedule(now + T_CHECK, SimEvent::Check(i));
+                continue;
+            }
+            SimEvent::Sample => {
+                network.schedule(now + config.t_gossip(), SimEvent::Sample);
+                sample(&nodes, &down, &mut metrics);
+                continue;
+            }
+            _ => continue,
+        };
+        network.send(&mut rng, &config.faults, observer, messages, now);
+
+        // a change belongs to the generation the observer knows the node with, the current
+        // one if it has not heard of the node since its own start, and is a false positive
+        // only if that generation is still running
+        for (j, state) in changes {
+            let generation = match nodes[observer].generation(j) {
+                Some(generation) if generation > 0 => generation,
+                _ => generations[j],
+            };
+            let episode = (j, generation);
+            let key = (observer, episode);
+            let crashed = metrics.crashed.get(&episode).cloned();
+            let left = metrics.left.get(&episode).cloned();
+            let restarted = metrics.restarted.get(&episode).cloned();
+            match (state, crashed, left) {
+                (State::Left, _, Some(at)) => {
+                    metrics.departed.entry(key).or_insert(now - at);
+                }
+                (State::Faulty, Some(at), _) => {
+                    metrics.detected.entry(key).or_insert(now - at);
+                }
+                (State::Faulty, None, None) => metrics.false_positives += 1,
+                (State::Faulty, None, Some(_)) => metrics.missed_leaves += 1,
+                (State::Removed, Some(at), _) => {
+                    metrics.removed.entry(key).or_insert(now - at);
+                }
+                (State::Running, _, _) => {
+                    if let Some(at) = restarted {
+                        metrics.recovered.entry(key).or_insert(now - at);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    (metrics, network.sent, network.bytes)
+}
+
+/// Compares the table of every running node with the own versions of the other running nodes.
+fn sample<D: Detector>(nodes: &[D], down: &BTreeSet<usize>, metrics: &mut Metrics) {
+    let tables: BTreeMap<usize, BTreeMap<usize, Version>> = (0..nodes.len())
+        .filter(|i| !down.contains(i))
+        .map(|i| (i, nodes[i].heartbeats()))
+        .collect();
+    for (i, table) in tables.iter() {
+        for (j, own) in tables.iter().filter(|(j, _)| *j != i) {
+            let (generation, heartbeat) = own[j];
+            match table.get(j) {
+                Some((g, h)) if *g == generation => metrics.lags.push(heartbeat.saturating_sub(*h)),
+                _ => metrics.missing += 1,
+            }
+        }
+    }
+}
+
+fn report(num: usize, metrics: &Metrics, sent: usize, bytes: usize, t_gossip: Duration) {
+    println!(
+        "Simulated {num} nodes, {} crashed, {} left",
+        metrics.crashed.len(),
+        metrics.left.len()
+    );
+    let latencies = |list: &BTreeMap<(usize, Episode), Duration>, episode: &Episode| {
+        list.iter()
+            .filter(|((_, e), _)| e == episode)
+            .map(|(_, d)| d.as_secs_f64())
+            .collect::<Vec<f64>>()
+    };
+    for (episode, at) in metrics.crashed.iter() {
+        println!(
+            "Node {} crashed at {:.3}s, detected by {}, removed by {}",
+            episode.0,
+            at.as_secs_f64(),
+            observers(&latencies(&metrics.detected, episode)),
+            observers(&latencies(&metrics.removed, episode))
+        );
+    }
+    for (episode, at) in metrics.left.iter() {
+        println!(
+            "Node {} left at {:.3}s, marked left by {}",
+            episode.0,
+            at.as_secs_f64(),
+            observers(&latencies(&metrics.departed, episode))
+        );
+    }
+    for (episode, at) in metrics.restarted.iter() {
+        println!(
+            "Node {} restarted at {:.3}s, running again on {}",
+            episode.0,
+            at.as_secs_f64(),
+            observers(&latencies(&metrics.recovered, episode))
+        );
+    }
+    println!("False positives {}", metrics.false_positives);
+    if !metrics.left.is_empty() {
+        println!("Left nodes set to faulty {}", metrics.missed_leaves);
+    }
+    println!("Messages sent {sent}, {bytes} bytes");
+
+    // heartbeat gossip increases the version once per round, so a lag of one version
+    // is about one gossip round of dissemination delay
+    let lags = &metrics.lags;
+    if !lags.is_empty() {
+        let avg = lags.iter().sum::<u64>() as f64 / lags.len() as f64;
+        let max = lags.iter().max().unwrap();
+        let current = lags.iter().filter(|l| **l == 0).count() as f64 / lags.len() as f64;
+        let missing = metrics.missing as f64 / (lags.len() + metrics.missing) as f64;
+        println!(
+            "Convergence avg lag {avg:.2} versions ({:.3}s) max {max}, {:.1}% of entries up to date, {:.1}% missing",
+            avg * t_gossip.as_secs_f64(),
+            100.0 * current,
+            100.0 * missing
+        );
+    }
+}
+
+/// Number of observers and their latencies, e.g. `3 nodes after min ...`.
+fn observers(latencies: &[f64]) -> String {
+    match latencies.len() {
+        0 => "no node".to_owned(),
+        n => format!("{n} nodes{}", summary(latencies)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Crash, membership::Membership};
+
+    const DURATION: Duration = Duration::from_secs(120);
+
+    /// Lossless network, nodes crash silently and never come back.
+    fn config() -> Config {
+        QUIET.store(true, Ordering::Relaxed);
+        Config {
+            crash: Crash::Random(0.01),
+            leave: false,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn crash_detected_by_every_node() {
+        // every node gossips to all others, so no node hears of the last heartbeat of a crashed
+        // node later than a message latency after the crash
+        let config = Config {
+            fanout: 7,
+            ..config()
+        };
+        let (metrics, _, _) = simulate::<Membership>(8, &config, 42, DURATION, None, false);
+        let bound = config.t_fail() + config.t_gossip();
+        let mut checked = 0;
+        for (episode, at) in metrics.crashed.iter() {
+            let (j, _) = episode;
+            let deadline = *at + bound;
+            if deadline + config.t_gossip() > DURATION {
+                continue;
+            }
+            let observers = (0..8).filter(|i| {
+                (i != j)
+                    & metrics
+                        .crashed
+                        .get(&(*i, 1))
+                        .is_none_or(|crashed| *crashed > deadline)
+            });
+            for i in observers {
+                let latency = metrics.detected.get(&(i, *episode));
+                assert!(
+                    latency.is_some_and(|latency| *latency <= bound),
+                    "node {i} detected node {j} after {latency:?}"
+                );
+                checked += 1;
+            }
+        }
+        assert!(checked > 0, "no crash to check");
+        assert_eq!(metrics.false_positives, 0);
+    }
+
+    #[test]
+    fn repeated_crashes_detected_per_generation() {
+        // nodes come back before they are removed, so late detections of an earlier crash
+        // and detections of a later crash must not count as false positives
+        let config = Config {
+            crash: Crash::Random(0.05),
+            fanout: 7,
+            ..config()
+        };
+        let restart = Duration::from_secs(15);
+        let (metrics, _, _) = simulate::<Membership>(8, &config, 1, DURATION, Some(restart), false);
+        let twice = (0..8).find(|j| {
+            metrics
+                .crashed
+                .keys()
+                .filter(|(i, generation)| (i == j) & (*generation <= 2))
+                .count()
+                == 2
+        });
+        let j = twice.expect("no node crashed twice");
+        for generation in [1, 2] {
+            assert!(
+                metrics.detected.keys().any(|(_, e)| *e == (j, generation)),
+                "crash of node {j} in generation {generation} not detected"
+            );
+        }
+        assert!(metrics.recovered.keys().any(|(_, e)| *e == (j, 2)));
+        assert_eq!(metrics.false_positives, 0);
+    }
+
+    #[test]
+    fn leave_spreads_despite_loss() {
+        // a fifth of the leave messages is lost, the nodes that miss one learn it through gossip
+        let config = Config {
+            leave: true,
+            fanout: 7,
+            faults: Faults {
+                loss: 0.2,
+                ..Faults::default()
+            },
+            ..config()
+        };
+        let (metrics, _, _) = simulate::<Membership>(8, &config, 3, DURATION, None, false);
+        let bound = config.t_fail();
+        let mut checked = 0;
+        for (episode, at) in metrics.left.iter() {
+            let deadline = *at + bound;
+            if deadline > DURATION {
+                continue;
+            }
+            let observers = (0..8).filter(|i| {
+                (*i != episode.0)
+                    & metrics
+                        .left
+                        .get(&(*i, 1))
+                        .is_none_or(|left| *left > deadline)
+            });
+            for i in observers {
+                assert!(
+                    metrics.departed.contains_key(&(i, *episode)),
+                    "node {i} did not learn that node {} left",
+                    episode.0
+                );
+                checked += 1;
+            }
+        }
+        assert!(checked > 0, "no leave to check");
+        assert_eq!(metrics.missed_leaves, 0);
+        assert_eq!(metrics.false_positives, 0);
+    }
+
+    #[test]
+    fn same_seed_same_metrics() {
+        let config = config();
+        let first = simulate::<Membership>(8, &config, 7, DURATION, None, true);
+        let second = simulate::<Membership>(8, &config, 7, DURATION, None, true);
+        assert_eq!(first, second);
+    }
+}
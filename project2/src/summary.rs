@@ -0,0 +1,11 @@
+/// Minimum, average and maximum of latencies in seconds as ` after min ..s avg ..s max ..s`,
+/// empty if there are none. Shared by the simulation and the harness.
+pub fn summary(latencies: &[f64]) -> String {
+    if latencies.is_empty() {
+        return String::new();
+    }
+    let min = latencies.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = latencies.iter().cloned().fold(0.0, f64::max);
+    let avg = latencies.iter().sum::<f64>() / latencies.len() as f64;
+    format!(" after min {min:.3}s avg {avg:.3}s max {max:.3}s")
+}
@@ -0,0 +1,574 @@
+use rand::{seq::IteratorRandom, Rng};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::{
+    config::Config,
+    log,
+    membership::{Detector, Packet, State, Status, Version},
+    wire::Tag,
+};
+
+/// Maximum number of membership updates piggybacked on a message.
+const MAX_UPDATES: usize = 6;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Kind {
+    /// `origin` is set when probing on behalf of another node.
+    Ping {
+        seq: u64,
+        origin: Option<usize>,
+    },
+    Ack {
+        seq: u64,
+        origin: Option<usize>,
+    },
+    PingReq {
+        seq: u64,
+        target: usize,
+    },
+    /// The sender leaves, its update is piggybacked.
+    Leave,
+}
+
+/// Disseminated state of a node, `Running` is alive and `Faulty` is confirmed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Update {
+    pub id: usize,
+    pub addr: String,
+    pub state: State,
+    pub generation: u64,
+    pub incarnation: u64,
+}
+
+impl Update {
+    fn version(&self) -> Version {
+        (self.generation, self.incarnation)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwimMessage {
+    pub msg_id: usize,
+    pub receiver_id: usize,
+    pub kind: Kind,
+    pub updates: Vec<Update>,
+}
+
+impl Packet for SwimMessage {
+    fn msg_id(&self) -> usize {
+        self.msg_id
+    }
+
+    fn receiver_id(&self) -> usize {
+        self.receiver_id
+    }
+
+    fn tag(&self) -> Tag {
+        match self.kind {
+            Kind::Ping { .. } | Kind::PingReq { .. } => Tag::Ping,
+            Kind::Ack { .. } => Tag::Ack,
+            Kind::Leave => Tag::Leave,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub state: State,
+    pub generation: u64,
+    pub incarnation: u64,
+    pub updated: Duration,
+    pub addr: String,
+}
+
+struct Probe {
+    target: usize,
+    seq: u64,
+    sent: Duration,
+    indirect: bool,
+}
+
+/// SWIM failure detector by Das et al., every protocol period a random member is pinged,
+/// without an ack within T_PING other members are asked to ping it and without any ack
+/// until the end of the period the member is suspected. Suspected members are faulty after
+/// T_FAIL unless they refute with a higher incarnation. State changes are piggybacked.
+pub struct Swim {
+    pub id: usize,
+    pub members: BTreeMap<usize, Member>,
+    msg_id: usize,
+    seq: u64,
+    probe: Option<Probe>,
+    /// updates to piggyback and how often they were sent
+    updates: Vec<(Update, usize)>,
+    t_ping: Duration,
+    t_suspect: Duration,
+    t_cleanup: Duration,
+    indirect: usize,
+}
+
+impl Swim {
+    fn message(&mut self, receiver_id: usize, kind: Kind) -> SwimMessage {
+        // every update is sent about 3 log(n) times
+        let limit = 3 * (usize::BITS - self.members.len().leading_zeros()) as usize;
+        self.updates.sort_by_key(|(_, sent)| *sent);
+        let updates = self
+            .updates
+            .iter_mut()
+            .take(MAX_UPDATES)
+            .map(|(update, sent)| {
+                *sent += 1;
+                update.clone()
+            })
+            .collect();
+        self.updates.retain(|(_, sent)| *sent < limit);
+
+        log(
+            self.id,
+            format!("Send msg_id {} {kind:?} to node {receiver_id}", self.msg_id).as_str(),
+        );
+        let message = SwimMessage {
+            msg_id: self.msg_id,
+            receiver_id,
+            kind,
+            updates,
+        };
+        self.msg_id += 1;
+        message
+    }
+
+    fn disseminate(&mut self, update: Update) {
+        self.updates.retain(|(u, _)| u.id != update.id);
+        self.updates.push((update, 0));
+    }
+
+    fn update(&self, i: usize) -> Update {
+        let member = &self.members[&i];
+        Update {
+            id: i,
+            addr: member.addr.clone(),
+            state: member.state.clone(),
+            generation: member.generation,
+            incarnation: member.incarnation,
+        }
+    }
+
+    /// Sets member `i` to `state` and disseminates the change.
+    fn set(&mut self, i: usize, state: State, now: Duration, changes: &mut Vec<(usize, State)>) {
+        let member = self.members.get_mut(&i).unwrap();
+        if member.state != state {
+            changes.push((i, state.clone()));
+        }
+        member.state = state;
+        member.updated = now;
+        if !matches!(member.state, State::Removed) {
+            self.disseminate(self.update(i));
+        }
+    }
+
+    /// Applies a received update, alive overrides suspect with a higher incarnation,
+    /// suspect overrides alive with the same incarnation and confirm overrides both.
+    fn apply(&mut self, update: Update, now: Duration, changes: &mut Vec<(usize, State)>) {
+        let j = update.id;
+        if j == self.id {
+            let own = self.members.get_mut(&j).unwrap();
+            // suspicions of an earlier generation are stale
+            if matches!(update.state, State::Suspect | State::Faulty)
+                & (update.generation == own.generation)
+                & (update.incarnation >= own.incarnation)
+            {
+                own.incarnation = update.incarnation + 1;
+                log(
+                    self.id,
+                    format!("Refuted suspicion with incarnation {}", own.incarnation).as_str(),
+                );
+                self.disseminate(self.update(j));
+            }
+            return;
+        }
+
+        let Some(member) = self.members.get(&j) else {
+            if matches!(update.state, State::Running | State::Suspect) {
+                log(
+                    self.id,
+                    format!(
+                        "Node {j} learned through gossip in generation {}",
+                        update.generation
+                    )
+                    .as_str(),
+                );
+                self.members.insert(
+                    j,
+                    Member {
+                        state: update.state.clone(),
+                        generation: update.generation,
+                        incarnation: update.incarnation,
+                        updated: now,
+                        addr: update.addr.clone(),
+                    },
+                );
+                changes.push((j, update.state.clone()));
+                self.disseminate(update);
+            }
+            return;
+        };
+
+        let newer = update.version() > (member.generation, member.incarnation);
+        let same = update.version() == (member.generation, member.incarnation);
+        let accept = match (&update.state, &member.state) {
+            (State::Running, State::Removed) => newer | same,
+            (State::Running, State::Left) => update.generation > member.generation,
+            (State::Running, _) => newer,
+            (State::Suspect, State::Running) => newer | same,
+            (State::Suspect, _) => newer,
+            (State::Faulty, State::Running | State::Suspect) => newer | same,
+            (State::Left, State::Left) => false,
+            (State::Left, _) => newer | same,
+            _ => false,
+        };
+        if !accept {
+            return;
+        }
+        if (update.generation > member.generation) & (member.generation > 0) {
+            log(
+                self.id,
+                format!("Node {j} restarted with generation {}", update.generation).as_str(),
+            );
+        }
+        let generation = update.generation;
+        match (&update.state, &member.state) {
+            (State::Running, State::Suspect) => log(
+                self.id,
+                format!(
+                    "Node {j} alive with incarnation {} in generation {generation}",
+                    update.incarnation
+                )
+                .as_str(),
+            ),
+            (State::Running, State::Faulty) => log(
+                self.id,
+                format!("Node {j} recovered in generation {generation}").as_str(),
+            ),
+            (State::Running, State::Removed | State::Left) => log(
+                self.id,
+                format!("Node {j} rejoined in generation {generation}").as_str(),
+            ),
+            (State::Suspect, State::Running) => {
+                log(self.id, format!("Node {j} suspected").as_str())
+            }
+            (State::Faulty, _) => log(
+                self.id,
+                format!("Node {j} set to faulty in generation {generation}").as_str(),
+            ),
+            (State::Left, _) => log(
+                self.id,
+                format!("Node {j} left in generation {generation}").as_str(),
+            ),
+            _ => {}
+        }
+        let member = self.members.get_mut(&j).unwrap();
+        member.generation = update.generation;
+        member.incarnation = update.incarnation;
+        member.addr = update.addr;
+        self.set(j, update.state, now, changes);
+    }
+}
+
+impl Detector for Swim {
+    type Message = SwimMessage;
+
+    /// Announces the own generation, so peers accept a restarted node right away.
+    fn new(id: usize, addr: String, generation: u64, config: &Config, now: Duration) -> Self {
+        let member = Member {
+            state: State::Running,
+            generation,
+            incarnation: 0,
+            updated: now,
+            addr,
+        };
+        let mut swim = Self {
+            id,
+            members: BTreeMap::from([(id, member)]),
+            msg_id: 0,
+            seq: 0,
+            probe: None,
+            updates: Vec::new(),
+            t_ping: config.t_ping(),
+            t_suspect: config.t_fail(),
+            t_cleanup: config.t_cleanup(),
+            indirect: config.indirect,
+        };
+        swim.disseminate(swim.update(id));
+        swim
+    }
+
+    fn addrs(&self) -> BTreeMap<usize, String> {
+        self.members
+            .iter()
+            .map(|(i, member)| (*i, member.addr.clone()))
+            .collect()
+    }
+
+    fn add(&mut self, i: usize, addr: String, now: Duration) {
+        if i == self.id {
+            return;
+        }
+        let member = self.members.entry(i).or_insert_with(|| Member {
+            state: State::Removed,
+            generation: 0,
+            incarnation: 0,
+            updated: now,
+            addr: addr.clone(),
+        });
+        member.addr = addr;
+        if matches!(member.state, State::Removed | State::Left) {
+            self.set(i, State::Running, now, &mut Vec::new());
+        }
+    }
+
+    fn heartbeats(&self) -> BTreeMap<usize, Version> {
+        self.members
+            .iter()
+            .filter(|(_, member)| !matches!(member.state, State::Removed | State::Left))
+            .map(|(i, member)| (*i, (member.generation, member.incarnation)))
+            .collect()
+    }
+
+    fn peers(&self) -> BTreeMap<usize, String> {
+        self.members
+            .iter()
+            .filter(|(_, member)| !matches!(member.state, State::Removed | State::Left))
+            .map(|(i, member)| (*i, member.addr.clone()))
+            .collect()
+    }
+
+    /// Join replies carry the generations and incarnations of all alive members.
+    fn merge(
+        &mut self,
+        heartbeats: BTreeMap<usize, Version>,
+        peers: &BTreeMap<usize, String>,
+        now: Duration,
+    ) -> Vec<(usize, State)> {
+        let mut changes = Vec::new();
+        for (j, (generation, incarnation)) in heartbeats {
+            let Some(addr) = peers.get(&j) else {
+                log(
+                    self.id,
+                    format!("Dropped node {j} without address").as_str(),
+                );
+                continue;
+            };
+            let update = Update {
+                id: j,
+                addr: addr.clone(),
+                state: State::Running,
+                generation,
+                incarnation,
+            };
+            self.apply(update, now, &mut changes);
+        }
+        changes
+    }
+
+    /// Suspects the target of the last probe if it did not answer and probes a random member.
+    fn tick<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        now: Duration,
+    ) -> (Vec<SwimMessage>, Vec<(usize, State)>) {
+        let mut changes = Vec::new();
+        if let Some(probe) = self.probe.take() {
+            if matches!(self.members[&probe.target].state, State::Running) {
+                log(self.id, format!("Node {} suspected", probe.target).as_str());
+                self.set(probe.target, State::Suspect, now, &mut changes);
+            }
+        }
+
+        let target = self
+            .members
+            .iter()
+            .filter(|(i, member)| {
+                !(*i).eq(&self.id) & matches!(member.state, State::Running | State::Suspect)
+            })
+            .map(|(i, _)| i)
+            .cloned()
+            .choose(rng);
+        let Some(target) = target else {
+            log(self.id, "No nodes in neighbor list");
+            return (Vec::new(), changes);
+        };
+        self.seq += 1;
+        self.probe = Some(Probe {
+            target,
+            seq: self.seq,
+            sent: now,
+            indirect: false,
+        });
+        let ping = Kind::Ping {
+            seq: self.seq,
+            origin: None,
+        };
+        (vec![self.message(target, ping)], changes)
+    }
+
+    fn receive<R: Rng>(
+        &mut self,
+        _rng: &mut R,
+        from: usize,
+        message: SwimMessage,
+        now: Duration,
+    ) -> (Vec<SwimMessage>, Vec<(usize, State)>) {
+        log(
+            self.id,
+            format!(
+                "Recv msg_id {} {:?} received from node {from}",
+                message.msg_id, message.kind
+            )
+            .as_str(),
+        );
+        let mut changes = Vec::new();
+        for update in message.updates {
+            self.apply(update, now, &mut changes);
+        }
+
+        let replies = match message.kind {
+            Kind::Ping { seq, origin } => vec![self.message(from, Kind::Ack { seq, origin })],
+            Kind::PingReq { seq, target } => {
+                let ping = Kind::Ping {
+                    seq,
+                    origin: Some(from),
+                };
+                vec![self.message(target, ping)]
+            }
+            Kind::Ack {
+                seq,
+                origin: Some(origin),
+            } => vec![self.message(origin, Kind::Ack { seq, origin: None })],
+            Kind::Ack { seq, origin: None } => {
+                if self.probe.as_ref().is_some_and(|probe| probe.seq == seq) {
+                    self.probe = None;
+                }
+                Vec::new()
+            }
+            Kind::Leave => {
+                // the piggybacked left update may have been crowded out by other updates
+                let known = self
+                    .members
+                    .get(&from)
+                    .is_some_and(|member| !matches!(member.state, State::Left | State::Removed));
+                if (from != self.id) & known {
+                    let generation = self.members[&from].generation;
+                    log(
+                        self.id,
+                        format!("Node {from} left in generation {generation}").as_str(),
+                    );
+                    self.set(from, State::Left, now, &mut changes);
+                }
+                Vec::new()
+            }
+        };
+        (replies, changes)
+    }
+
+    /// Sends ping-req after T_PING, sets suspected members faulty after T_FAIL
+    /// and removes faulty members after T_CLEANUP.
+    fn check<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        now: Duration,
+    ) -> (Vec<SwimMessage>, Vec<(usize, State)>) {
+        let mut messages = Vec::new();
+        let mut changes = Vec::new();
+        let mut ping_req = None;
+        if let Some(probe) = self.probe.as_mut() {
+            if !probe.indirect & (now.saturating_sub(probe.sent) > self.t_ping) {
+                probe.indirect = true;
+                ping_req = Some((probe.target, probe.seq));
+            }
+        }
+        if let Some((target, seq)) = ping_req {
+            let helpers = self
+                .members
+                .iter()
+                .filter(|(i, member)| {
+                    !(*i).eq(&self.id) & !(*i).eq(&target) & matches!(member.state, State::Running)
+                })
+                .map(|(i, _)| *i)
+                .choose_multiple(rng, self.indirect);
+            log(
+                self.id,
+                format!("No ack from node {target}, ping-req through {helpers:?}").as_str(),
+            );
+            for helper in helpers {
+                messages.push(self.message(helper, Kind::PingReq { seq, target }));
+            }
+        }
+
+        let expired: Vec<(usize, State)> = self
+            .members
+            .iter()
+            .filter(|(i, _)| !(*i).eq(&self.id))
+            .filter_map(|(i, member)| {
+                let elapsed = now.saturating_sub(member.updated);
+                match member.state {
+                    State::Suspect if elapsed > self.t_suspect => Some((*i, State::Faulty)),
+                    State::Faulty if elapsed > self.t_cleanup => Some((*i, State::Removed)),
+                    _ => None,
+                }
+            })
+            .collect();
+        for (i, state) in expired {
+            let generation = self.members[&i].generation;
+            match state {
+                State::Faulty => log(
+                    self.id,
+                    format!("Node {i} set to faulty in generation {generation}").as_str(),
+                ),
+                _ => log(
+                    self.id,
+                    format!("Node {i} removed from neighbor list in generation {generation}")
+                        .as_str(),
+                ),
+            }
+            self.set(i, state, now, &mut changes);
+        }
+        (messages, changes)
+    }
+
+    /// Sends the own left update to every alive member, they keep disseminating it.
+    fn leave(&mut self) -> Vec<SwimMessage> {
+        self.set(self.id, State::Left, Duration::ZERO, &mut Vec::new());
+        let receivers: Vec<usize> = self
+            .peers()
+            .into_keys()
+            .filter(|i| !i.eq(&self.id))
+            .collect();
+        receivers
+            .into_iter()
+            .map(|i| self.message(i, Kind::Leave))
+            .collect()
+    }
+
+    fn table(&self, now: Duration) -> BTreeMap<usize, Status> {
+        self.members
+            .iter()
+            .map(|(i, member)| {
+                let status = Status {
+                    addr: member.addr.clone(),
+                    state: member.state.clone(),
+                    generation: member.generation,
+                    heartbeat: member.incarnation,
+                    age: match *i == self.id {
+                        true => 0.0,
+                        false => now.saturating_sub(member.updated).as_secs_f64(),
+                    },
+                };
+                (*i, status)
+            })
+            .collect()
+    }
+
+    fn generation(&self, i: usize) -> Option<u64> {
+        self.members.get(&i).map(|member| member.generation)
+    }
+}
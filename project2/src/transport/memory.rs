@@ -0,0 +1,55 @@
+use std::{
+    io,
+    sync::mpsc::{channel, Receiver, Sender},
+    time::Duration,
+};
+
+use super::{Traffic, Transport};
+
+/// In-memory transport between nodes running as threads of one process,
+/// addresses are ignored and node ids index the channels, so seeds known only
+/// by address can not be reached.
+pub struct Memory {
+    inbox: Receiver<Vec<u8>>,
+    peers: Vec<Sender<Vec<u8>>>,
+    traffic: Traffic,
+}
+
+/// Connects `num` in-memory transports with each other, the transport of node i is at index i.
+pub fn network(num: usize) -> Vec<Memory> {
+    let (peers, inboxes): (Vec<_>, Vec<_>) = (0..num).map(|_| channel()).unzip();
+    inboxes
+        .into_iter()
+        .map(|inbox| Memory {
+            inbox,
+            peers: peers.clone(),
+            traffic: Traffic::default(),
+        })
+        .collect()
+}
+
+impl Transport for Memory {
+    fn send(&mut self, to: usize, _addr: &str, frame: &[u8]) -> io::Result<()> {
+        let peer = self.peers.get(to).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no node {to} in the in-memory network"),
+            )
+        })?;
+        // a terminated node dropped its inbox, the frame is lost like on a real network
+        if peer.send(frame.to_vec()).is_ok() {
+            self.traffic.record_sent(frame.len());
+        }
+        Ok(())
+    }
+
+    fn recv(&mut self, timeout: Duration) -> Option<Vec<u8>> {
+        let frame = self.inbox.recv_timeout(timeout).ok()?;
+        self.traffic.record_received(frame.len());
+        Some(frame)
+    }
+
+    fn traffic(&self) -> &Traffic {
+        &self.traffic
+    }
+}
@@ -0,0 +1,50 @@
+use std::{fmt, io, time::Duration};
+
+mod memory;
+mod udp;
+mod zeromq;
+
+pub use memory::network;
+pub use udp::Udp;
+pub use zeromq::Zmq;
+
+/// Directed delivery of frames between nodes, the frames are encoded and decoded by the
+/// wire format.
+pub trait Transport {
+    /// Sends a frame to node `to` reachable at `addr`, frames to unreachable nodes are lost.
+    fn send(&mut self, to: usize, addr: &str, frame: &[u8]) -> io::Result<()>;
+    /// Waits up to `timeout` for the next frame.
+    fn recv(&mut self, timeout: Duration) -> Option<Vec<u8>>;
+    fn traffic(&self) -> &Traffic;
+}
+
+/// Messages and bytes moved by a node.
+#[derive(Debug, Default)]
+pub struct Traffic {
+    sent: usize,
+    sent_bytes: usize,
+    received: usize,
+    received_bytes: usize,
+}
+
+impl Traffic {
+    fn record_sent(&mut self, bytes: usize) {
+        self.sent += 1;
+        self.sent_bytes += bytes;
+    }
+
+    fn record_received(&mut self, bytes: usize) {
+        self.received += 1;
+        self.received_bytes += bytes;
+    }
+}
+
+impl fmt::Display for Traffic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "sent {} messages {} bytes, received {} messages {} bytes",
+            self.sent, self.sent_bytes, self.received, self.received_bytes
+        )
+    }
+}
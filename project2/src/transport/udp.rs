@@ -0,0 +1,68 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::Duration,
+};
+
+use super::{Traffic, Transport};
+
+/// Largest datagram that can be received.
+const MAX_DATAGRAM: usize = 65507;
+
+/// UDP transport, every frame is a single datagram. Lost datagrams are not resent,
+/// as in most gossip systems.
+pub struct Udp {
+    socket: UdpSocket,
+    /// Resolved peer addresses.
+    resolved: HashMap<String, SocketAddr>,
+    buffer: Vec<u8>,
+    traffic: Traffic,
+}
+
+impl Udp {
+    /// Binds to the same address as the ZMQ inbox, e.g. tcp://*:5550 becomes 0.0.0.0:5550.
+    pub fn bind(bind: &str) -> Udp {
+        let bind = bind.trim_start_matches("tcp://").replace('*', "0.0.0.0");
+        let socket = UdpSocket::bind(bind).expect("failed binding udp socket");
+        Udp {
+            socket,
+            resolved: HashMap::new(),
+            buffer: vec![0; MAX_DATAGRAM],
+            traffic: Traffic::default(),
+        }
+    }
+}
+
+impl Transport for Udp {
+    fn send(&mut self, _to: usize, addr: &str, frame: &[u8]) -> io::Result<()> {
+        let target = match self.resolved.get(addr) {
+            Some(target) => *target,
+            None => {
+                // the socket is bound to ipv4, localhost may resolve to ipv6 first
+                let target = addr
+                    .to_socket_addrs()?
+                    .find(SocketAddr::is_ipv4)
+                    .ok_or_else(|| io::Error::other(format!("no ipv4 address for {addr}")))?;
+                self.resolved.insert(addr.to_owned(), target);
+                target
+            }
+        };
+        self.socket.send_to(frame, target)?;
+        self.traffic.record_sent(frame.len());
+        Ok(())
+    }
+
+    fn recv(&mut self, timeout: Duration) -> Option<Vec<u8>> {
+        // a zero timeout would block forever
+        let timeout = timeout.max(Duration::from_millis(1));
+        self.socket.set_read_timeout(Some(timeout)).unwrap();
+        let (len, _) = self.socket.recv_from(&mut self.buffer).ok()?;
+        self.traffic.record_received(len);
+        Some(self.buffer[..len].to_vec())
+    }
+
+    fn traffic(&self) -> &Traffic {
+        &self.traffic
+    }
+}
@@ -0,0 +1,66 @@
+use std::{collections::HashMap, io, time::Duration};
+
+use super::{Traffic, Transport};
+
+/// Messages queued for a peer that is not connected, further ones are dropped.
+const SEND_QUEUE: i32 = 10;
+
+/// ZMQ transport, every node binds a single PULL socket and only gets the frames addressed
+/// to it. A PUSH socket is connected to every peer on its first frame and reconnected
+/// when its address changes.
+pub struct Zmq {
+    context: zmq::Context,
+    inbox: zmq::Socket,
+    outbox: HashMap<usize, (String, zmq::Socket)>,
+    traffic: Traffic,
+}
+
+impl Zmq {
+    pub fn bind(bind: &str) -> Zmq {
+        let context = zmq::Context::new();
+        let inbox = context.socket(zmq::PULL).unwrap();
+        inbox.bind(bind).expect("failed binding inbox");
+        Zmq {
+            context,
+            inbox,
+            outbox: HashMap::new(),
+            traffic: Traffic::default(),
+        }
+    }
+}
+
+impl Transport for Zmq {
+    fn send(&mut self, to: usize, addr: &str, frame: &[u8]) -> io::Result<()> {
+        if self.outbox.get(&to).is_none_or(|(a, _)| a != addr) {
+            let socket = self.context.socket(zmq::PUSH)?;
+            socket.set_sndhwm(SEND_QUEUE)?;
+            socket.set_linger(0)?;
+            socket.connect(format!("tcp://{addr}").as_str())?;
+            self.outbox.insert(to, (addr.to_owned(), socket));
+        }
+        let (_, socket) = &self.outbox[&to];
+        match socket.send(frame, zmq::DONTWAIT) {
+            Ok(()) => {
+                self.traffic.record_sent(frame.len());
+                Ok(())
+            }
+            // the queue of an unreachable peer is full
+            Err(zmq::Error::EAGAIN) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn recv(&mut self, timeout: Duration) -> Option<Vec<u8>> {
+        let timeout = timeout.as_millis().try_into().unwrap_or(i64::MAX);
+        if self.inbox.poll(zmq::POLLIN, timeout).ok()? == 0 {
+            return None;
+        }
+        let frame = self.inbox.recv_bytes(zmq::DONTWAIT).ok()?;
+        self.traffic.record_received(frame.len());
+        Some(frame)
+    }
+
+    fn traffic(&self) -> &Traffic {
+        &self.traffic
+    }
+}
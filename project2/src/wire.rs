@@ -0,0 +1,174 @@
+use bincode::Options;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+
+/// Largest frame that is decoded, so a corrupt length prefix can not exhaust the memory.
+const MAX_FRAME: u64 = 1 << 20;
+
+/// Version of the wire format, the first byte of every frame. Frames of other versions
+/// are dropped, so nodes of incompatible versions ignore each other.
+pub const VERSION: u8 = 2;
+
+/// Type of a message, encoded after the version.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Tag {
+    /// Heartbeat gossip including push-pull replies and shuffles.
+    Gossip,
+    Join,
+    /// Membership list answering a join.
+    Members,
+    Leave,
+    /// SWIM probe, direct or on behalf of another node.
+    Ping,
+    Ack,
+}
+
+/// Frame that could not be decoded.
+#[derive(Debug)]
+pub enum Error {
+    Empty,
+    Version(u8),
+    Malformed(bincode::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Empty => write!(f, "empty frame"),
+            Error::Version(version) => {
+                write!(f, "protocol version {version} instead of {VERSION}")
+            }
+            Error::Malformed(e) => write!(f, "malformed frame: {e}"),
+        }
+    }
+}
+
+impl From<bincode::Error> for Error {
+    fn from(e: bincode::Error) -> Self {
+        Error::Malformed(e)
+    }
+}
+
+/// Integers are variable length encoded, so small ids and counters take a single byte.
+fn options() -> impl Options {
+    bincode::DefaultOptions::new()
+}
+
+/// Encodes a message of node `from` as version byte, tag, sender and body.
+pub fn encode<T: Serialize>(tag: Tag, from: usize, body: &T) -> Vec<u8> {
+    let mut frame = vec![VERSION];
+    options()
+        .serialize_into(&mut frame, &(tag, from))
+        .expect("failed encoding header");
+    options()
+        .serialize_into(&mut frame, body)
+        .expect("failed encoding message");
+    frame
+}
+
+/// Checks the version and returns the tag, the sender and the still encoded body.
+pub fn decode(frame: &[u8]) -> Result<(Tag, usize, &[u8]), Error> {
+    let (version, mut rest) = frame.split_first().ok_or(Error::Empty)?;
+    if *version != VERSION {
+        return Err(Error::Version(*version));
+    }
+    let (tag, from) = options()
+        .with_limit(MAX_FRAME)
+        .deserialize_from(&mut rest)?;
+    Ok((tag, from, rest))
+}
+
+/// Decodes the body of a frame, trailing bytes are rejected.
+pub fn body<T: DeserializeOwned>(body: &[u8]) -> Result<T, Error> {
+    Ok(options()
+        .with_limit(MAX_FRAME)
+        .reject_trailing_bytes()
+        .deserialize(body)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        membership::{Detector, Kind, Membership, Message},
+        shiviz::Stamped,
+        swim::Swim,
+        QUIET,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::{collections::BTreeMap, sync::atomic::Ordering, time::Duration};
+
+    fn gossip(kind: Kind, heartbeats: BTreeMap<usize, (u64, u64)>) -> Vec<u8> {
+        let message = Message {
+            msg_id: 0,
+            receiver_id: 0,
+            kind,
+            heartbeats,
+            peers: BTreeMap::new(),
+            sample: Vec::new(),
+            left: BTreeMap::new(),
+        };
+        let stamped = Stamped {
+            clock: None,
+            message,
+        };
+        encode(Tag::Gossip, 1, &stamped)
+    }
+
+    fn received(frame: &[u8]) -> Result<Message, Error> {
+        let (_, _, bytes) = decode(frame)?;
+        body::<Stamped<Message>>(bytes).map(|stamped| stamped.message)
+    }
+
+    #[test]
+    fn truncated_frame_rejected() {
+        let frame = gossip(Kind::Gossip, BTreeMap::from([(1, (1, 1))]));
+        assert!(received(&frame).is_ok());
+        for end in 0..frame.len() {
+            assert!(received(&frame[..end]).is_err());
+        }
+        assert!(matches!(decode(&[]), Err(Error::Empty)));
+        // a length prefix far beyond the frame is rejected before allocating
+        let mut corrupt = vec![0xfd];
+        corrupt.extend((1u64 << 40).to_le_bytes());
+        assert!(matches!(body::<String>(&corrupt), Err(Error::Malformed(_))));
+    }
+
+    #[test]
+    fn wrong_version_rejected() {
+        let mut frame = gossip(Kind::Gossip, BTreeMap::new());
+        frame[0] = VERSION + 1;
+        assert!(matches!(decode(&frame), Err(Error::Version(v)) if v == VERSION + 1));
+    }
+
+    #[test]
+    fn inconsistent_frame_skipped() {
+        QUIET.store(true, Ordering::Relaxed);
+        let config = Config::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        let now = Duration::ZERO;
+        let mut membership = Membership::new(0, config.peer(0), 1, &config, now);
+        membership.add(1, config.peer(1), now);
+
+        // leave without the heartbeat of its sender
+        let frame = gossip(Kind::Leave, BTreeMap::new());
+        let leave = received(&frame).unwrap();
+        let (_, changes) = membership.receive(&mut rng, 1, leave, now);
+        assert!(changes.is_empty());
+
+        // heartbeat of an unknown node without its address
+        let frame = gossip(Kind::Gossip, BTreeMap::from([(5, (1, 1))]));
+        let message = received(&frame).unwrap();
+        let (_, changes) = membership.receive(&mut rng, 1, message.clone(), now);
+        assert!(changes.is_empty());
+        assert!(membership
+            .merge(message.heartbeats.clone(), &message.peers, now)
+            .is_empty());
+
+        let mut swim = Swim::new(0, config.peer(0), 1, &config, now);
+        assert!(swim
+            .merge(message.heartbeats, &message.peers, now)
+            .is_empty());
+    }
+}